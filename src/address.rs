@@ -1,15 +1,25 @@
 use error_stack::ResultExt;
 use roxmltree::Node;
 use serde::{Deserialize, Serialize};
-use std::net::IpAddr;
+use serde_with::{skip_serializing_none, DeserializeFromStr, SerializeDisplay};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+use strum_macros::EnumString;
 
 use crate::{Attribute, Error, Result};
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[skip_serializing_none]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Address {
     IpAddr(IpAddr),
-    MacAddr(String),
+    MacAddr {
+        addr: MacAddr,
+        vendor: Option<String>,
+    },
 }
 
 impl Address {
@@ -24,16 +34,158 @@ impl Address {
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("addr", "address")))?;
 
-        #[allow(clippy::single_match_else)]
-        match addrtype {
-            "mac" => Ok(Address::MacAddr(addr.to_string())),
-            _ => {
-                let addr = addr
-                    .parse::<IpAddr>()
-                    .change_context(Error::FailedToParseAttribute)
-                    .attach_printable(Attribute(("addr", "address")))?;
-                Ok(Address::IpAddr(addr))
+        let addrtype = addrtype
+            .parse::<AddrType>()
+            .change_context(Error::UnsupportedAddressType)
+            .attach_printable_lazy(|| format!("addrtype: \"{addrtype}\""))
+            .attach_printable(Attribute(("addrtype", "address")))?;
+
+        let address = match addrtype {
+            AddrType::Ipv4 => addr
+                .parse::<Ipv4Addr>()
+                .map(IpAddr::V4)
+                .change_context(Error::FailedToParseAttribute)
+                .map(Address::IpAddr),
+            AddrType::Ipv6 => addr
+                .parse::<Ipv6Addr>()
+                .map(IpAddr::V6)
+                .change_context(Error::FailedToParseAttribute)
+                .map(Address::IpAddr),
+            AddrType::Mac => addr
+                .parse::<MacAddr>()
+                .change_context(Error::FailedToParseAttribute)
+                .map(|addr| Address::MacAddr {
+                    addr,
+                    vendor: node.attribute("vendor").map(Into::into),
+                }),
+        };
+
+        address.attach_printable(Attribute(("addr", "address")))
+    }
+}
+
+#[derive(EnumString, Clone, Copy, Debug, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+enum AddrType {
+    Ipv4,
+    Ipv6,
+    Mac,
+}
+
+/// A 6-byte MAC address, formatted as uppercase colon-separated octets like nmap does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, SerializeDisplay, DeserializeFromStr)]
+pub struct MacAddr(pub [u8; 6]);
+
+#[derive(thiserror::Error, Debug)]
+#[error("invalid MAC address syntax")]
+pub struct MacAddrParseError;
+
+impl FromStr for MacAddr {
+    type Err = MacAddrParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // `00:11:22:33:44:55` or `00-11-22-33-44-55`, never a mix of both
+        let separator = match s.as_bytes().get(2) {
+            Some(b':') => ':',
+            Some(b'-') => '-',
+            _ => return Err(MacAddrParseError),
+        };
+
+        let mut octets = [0u8; 6];
+        let mut parts = s.split(separator);
+
+        for octet in &mut octets {
+            let part = parts.next().ok_or(MacAddrParseError)?;
+            // `from_str_radix` alone would accept a sign like `+1`
+            if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(MacAddrParseError);
+            }
+            *octet = u8::from_str_radix(part, 16).map_err(|_| MacAddrParseError)?;
+        }
+
+        if parts.next().is_some() {
+            return Err(MacAddrParseError);
+        }
+
+        Ok(MacAddr(octets))
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, octet) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
             }
+            write!(f, "{octet:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        address::{Address, MacAddr},
+        fixture, Error, NmapRun,
+    };
+
+    fn parse(addresses: &str) -> crate::Result<Vec<Address>> {
+        let xml = fixture::run(&format!(r#"<host><status state="up"/>{addresses}</host>"#));
+        NmapRun::parse(&xml).map(|report| report.hosts.unwrap().remove(0).addresses)
+    }
+
+    #[test]
+    fn parse_addresses() {
+        let addresses = parse(
+            r#"<address addr="10.0.0.1" addrtype="ipv4"/>
+<address addr="fe80::1" addrtype="ipv6"/>
+<address addr="00:1a:2B:3c:4D:5e" addrtype="mac" vendor="Acme"/>
+<address addr="00-11-22-33-44-55" addrtype="mac"/>"#,
+        )
+        .unwrap();
+
+        assert_eq!(addresses[0], Address::IpAddr("10.0.0.1".parse().unwrap()));
+        assert_eq!(addresses[1], Address::IpAddr("fe80::1".parse().unwrap()));
+        assert_eq!(
+            addresses[2],
+            Address::MacAddr {
+                addr: MacAddr([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]),
+                vendor: Some("Acme".to_string()),
+            }
+        );
+        let Address::MacAddr { addr, vendor } = &addresses[3] else {
+            panic!("expected a MAC address, got {:?}", addresses[3]);
+        };
+        assert_eq!(addr.to_string(), "00:11:22:33:44:55");
+        assert_eq!(*vendor, None);
+
+        let e = parse(r#"<address addr="10.0.0.1" addrtype="ipx"/>"#).unwrap_err();
+        assert!(matches!(e.current_context(), Error::UnsupportedAddressType));
+        let e = parse(r#"<address addr="fe80::1" addrtype="ipv4"/>"#).unwrap_err();
+        assert!(matches!(e.current_context(), Error::FailedToParseAttribute));
+    }
+
+    #[test]
+    fn parse_mac() {
+        assert_eq!(
+            "aa:bb:cc:dd:ee:ff".parse::<MacAddr>().unwrap(),
+            MacAddr([0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF])
+        );
+
+        for invalid in [
+            "",
+            "00:11:22:33:44",
+            "00:11:22:33:44:55:66",
+            "00:11-22:33:44:55",
+            "00-11-22-33-44:55",
+            "+0:11:22:33:44:55",
+            "00:+1:22:33:44:55",
+            "0:11:22:33:44:555",
+            "00:11:22:33:44:5g",
+            "0011.2233.4455",
+        ] {
+            assert!(invalid.parse::<MacAddr>().is_err(), "{invalid}");
         }
     }
 }
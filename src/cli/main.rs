@@ -4,14 +4,27 @@
 #![allow(clippy::missing_errors_doc)]
 
 use chrono::Utc;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use clio::{has_extension, ClioPath};
-use error_stack::ResultExt;
-use n2j::NmapRun;
+use error_stack::{AttachmentKind, FrameKind, Report, ResultExt};
+use n2j::{
+    context::ParseOptions,
+    diff::{Change, HostChange, PortChange, ReportDiff},
+    filter::Expr,
+    format::{read_json, Format, ReportWriter},
+    host::Host,
+    merge::SourceReport,
+    stream::Header,
+    table::{Column, TableOptions},
+    tail::Tail,
+    NmapRun,
+};
 use std::{
     fs::OpenOptions,
-    io::{Read, Write},
+    io::{BufRead, BufReader, Read, Write},
     panic::Location,
+    str::FromStr,
+    time::Duration,
 };
 use color_print::cformat;
 
@@ -28,19 +41,44 @@ pub enum Error {
     #[error("file create error: unable to create output file")]
     CreateOutputFile,
 
-    #[error("serialization error: failed to convert data to JSON format")]
+    #[error("serialization error: failed to convert data to the output format")]
     Serialization,
 
+    #[error("deserialization error: failed to read n2j JSON report")]
+    Deserialization,
+
     #[error("XML parsing error: unable to parse the provided XML content")]
     Parsing,
 
+    #[error("gnmap parsing error: unable to parse the provided grepable output")]
+    GnmapParsing,
+
+    #[error("normal output parsing error: unable to parse the provided nmap output")]
+    NormalParsing,
+
+    #[error("masscan parsing error: unable to parse the provided masscan JSON")]
+    MasscanParsing,
+
+    #[error("rustscan parsing error: unable to parse the provided greppable output")]
+    RustscanParsing,
+
     #[error("file write error: unable to access or write the file")]
     WriteFile,
+
+    #[error("unsupported format: --stream and --follow write hosts, use ndjson-host, ndjson-port, flat-host, flat-port, csv or tsv")]
+    UnsupportedFormat,
+
+    #[error("unsupported format: toml and nmap XML hold one report, write several inputs to an output directory or use another format")]
+    SingleReportFormat,
+
+    #[error("unsupported input: masscan reports can't be read with --stream or --follow")]
+    UnsupportedStream,
 }
 
 
 /// A command-line tool to convert NMAP XML output to JSON format.
 #[derive(Parser, Debug)]
+#[allow(clippy::struct_excessive_bools)]
 #[command(version, 
             about, 
             long_about = None, 
@@ -62,23 +100,175 @@ r#"<bold><underline>Examples:</underline></bold>
   <bold>[!]</bold> The default output format is NDJSON
 
     <bold>n2j /reports --output=reports.json</bold>
+
+  # Convert a huge report with constant memory, one host per line (NDJSON)
+
+    <bold>n2j sweep.xml --stream --output=hosts.json</bold>
+
+  # Keep only hosts with SMB open, fields and operators are listed in the docs of n2j::filter
+
+    <bold>n2j scan.xml --where 'port = 445 and protocol = tcp and state = open'</bold>
+
+  # Export open ports to a spreadsheet, --format tsv writes tab-separated values
+
+    <bold>n2j scan.xml --format csv --columns ip,hostnames,port,service,product,version --output=ports.csv</bold>
+
+  # Ship every open port of a running scan to a log store as a self-contained
+  # document with its host and the run context (args, start, version, scan type)
+
+    <bold>n2j scan.xml --follow --format flat-port >> /var/log/n2j/ports.ndjson</bold>
+
+  # Write YAML, TOML, MessagePack (msgpack) or CBOR instead of JSON
+  # A TOML file holds one report, several inputs need an output directory
+
+    <bold>n2j scan.xml --format yaml --output=scan.yaml</bold>
+
+  # Convert n2j JSON back to nmap XML, e.g. after redaction
+  # An XML file holds one report, several reports need an output directory
+
+    <bold>n2j report.json --to xml --output=report.xml</bold>
+
+  # Merge reports of scans split per subnet or port range into one inventory
+
+    <bold>n2j merge subnet-a.xml subnet-b.xml /scans/ports --output=inventory.json</bold>
+
+  # Show what changed since last week, add --json for machine-readable changes
+
+    <bold>n2j diff last-week.xml today.xml</bold>
 "#))]
 struct Args {
     /// A list of input files, directories, or stdin to parse.
     #[clap(value_parser, default_value = "-")]
     inputs: Vec<ClioPath>,
 
-    /// Output JSON to file, directory or stdout.
-    #[clap(long, short, value_parser, default_value = "-")]
+    /// Output to file, directory or stdout.
+    #[clap(long, short, value_parser, default_value = "-", global = true)]
     output: ClioPath,
 
     /// Additional context for errors
-    #[clap(long, value_parser, default_value = "false")]
+    #[clap(long, value_parser, default_value = "false", global = true)]
     debug: bool,
 
-    /// Pretty format JSON output
-    #[clap(long, value_parser, default_value = "false")]
+    /// Pretty format JSON output, same as `--format pretty`
+    #[clap(long, value_parser, default_value = "false", global = true)]
     pretty: bool,
+
+    /// Read reports host by host and write one host per line (NDJSON) or another host format
+    #[clap(long, value_parser, default_value = "false", conflicts_with = "pretty")]
+    stream: bool,
+
+    /// Watch a report that nmap is still writing and output hosts as they complete (NDJSON)
+    #[clap(long, value_parser, default_value = "false", conflicts_with = "pretty")]
+    follow: bool,
+
+    /// Stop `--follow` after this many seconds without new data [default: wait forever]
+    #[clap(long, value_name = "SECONDS", requires = "follow")]
+    idle_timeout: Option<u64>,
+
+    /// Salvage complete hosts from truncated or corrupted reports, report dropped data as warnings
+    #[clap(long, value_parser, default_value = "false", conflicts_with_all = ["stream", "follow"])]
+    recover: bool,
+
+    /// Skip broken elements and unknown values instead of failing, report them as warnings
+    #[clap(long, value_parser, default_value = "false", conflicts_with_all = ["stream", "follow", "recover"])]
+    lenient: bool,
+
+    /// Conversion direction, `xml` takes n2j JSON reports and writes nmap XML
+    #[clap(long, value_enum, default_value = "json", conflicts_with_all = ["pretty", "stream", "follow", "recover", "lenient"])]
+    to: Target,
+
+    /// Keep only hosts and ports that match an expression, e.g. 'port in 1-1024 and state = open'
+    #[clap(long = "where", value_name = "EXPR", value_parser = parse_filter, conflicts_with = "to")]
+    filter: Option<Expr>,
+
+    /// Output format: json, pretty, ndjson (a report per line, default), ndjson-host,
+    /// ndjson-port, flat-host, flat-port (with the run context), yaml, toml, msgpack, cbor,
+    /// csv or tsv (a row per open port), nmap XML is written with `--to xml`
+    #[clap(long, value_parser = parse_format, conflicts_with_all = ["pretty", "to"])]
+    format: Option<Format>,
+
+    /// Columns of `csv` and `tsv` output, e.g. ip,port,service [default: all]
+    #[clap(long, value_parser = parse_column, value_delimiter = ',')]
+    columns: Vec<Column>,
+
+    /// Write a row for up hosts without open ports in `csv` and `tsv` output
+    #[clap(long, value_parser, default_value = "false")]
+    hosts_without_ports: bool,
+
+    /// Keep values starting with =, +, -, @ as they are in `csv` and `tsv` output,
+    /// by default they are prefixed with ' so spreadsheets don't run them as formulas
+    #[clap(long, value_parser, default_value = "false")]
+    allow_formulas: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Merge reports into one, hosts are unified by IP address and ports by protocol and number
+    Merge {
+        /// Reports or directories of reports to merge
+        #[clap(value_parser, required = true)]
+        reports: Vec<ClioPath>,
+    },
+    /// Show what changed between an older and a newer report of the same targets
+    Diff {
+        /// The older report
+        #[clap(value_parser)]
+        old: ClioPath,
+
+        /// The newer report
+        #[clap(value_parser)]
+        new: ClioPath,
+
+        /// Write the changes as JSON instead of a colored summary
+        #[clap(long, value_parser, default_value = "false")]
+        json: bool,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Target {
+    Json,
+    Xml,
+}
+
+impl Target {
+    /// Extensions of the files this direction reads.
+    fn input_extensions(self) -> &'static [&'static str] {
+        match self {
+            Target::Json => &["xml", "gnmap", "nmap"],
+            Target::Xml => &["json"],
+        }
+    }
+}
+
+/// The output format, by default a JSON report per line, or a host per line with
+/// `--stream` and `--follow`.
+fn output_format(args: &Args) -> Format {
+    match args.format {
+        Some(format) => format,
+        None if args.to == Target::Xml => Format::Xml,
+        None if args.stream || args.follow => Format::NdjsonHost,
+        None if args.pretty => Format::Pretty,
+        None => Format::Ndjson,
+    }
+}
+
+/// A writer of the output format, CSV and TSV with the `--columns` of the command line.
+fn writer<'a>(args: &Args, output: impl Write + 'a) -> Box<dyn ReportWriter + 'a> {
+    let options = TableOptions {
+        columns: if args.columns.is_empty() {
+            Column::ALL.to_vec()
+        } else {
+            args.columns.clone()
+        },
+        hosts_without_ports: args.hosts_without_ports,
+        escape_formulas: !args.allow_formulas,
+        ..TableOptions::default()
+    };
+    output_format(args).writer(output, options)
 }
 
 fn main() {
@@ -87,22 +277,42 @@ fn main() {
     let args = Args::parse();
     let debug = args.debug;
 
-    match run(args) {
-        Err(e) => {
-            if debug {
-                eprintln!("n2j: {e:#?}");
-                std::process::exit(1);
-            } else {
-                eprintln!("n2j: {e}");
-                std::process::exit(1);
-            }
+    let result = match &args.command {
+        Some(Command::Merge { reports }) => merge(reports, &args),
+        Some(Command::Diff { old, new, json }) => diff(old, new, *json, &args),
+        None => run(&args),
+    };
+
+    if let Err(e) = result {
+        if debug {
+            eprintln!("n2j: {e:#?}");
+        } else if let Some(location) = e.downcast_ref::<n2j::warning::Location>() {
+            eprintln!("n2j: {e} at {location}");
+        } else {
+            eprintln!("n2j: {e}");
         }
-        _ => {}
+        std::process::exit(1);
     }
 }
 
-fn run(args: Args) -> Result<()> {
-    let mut output: Option<Box<dyn Write>> = if args.output.is_dir() {
+fn run(args: &Args) -> Result<()> {
+    let format = output_format(args);
+    if (args.stream || args.follow) && !format.writes_hosts() {
+        return Err(Report::new(Error::UnsupportedFormat))
+            .attach_printable(format!("format: {format}"));
+    }
+
+    let mut files = Vec::new();
+    for input in &args.inputs {
+        files.extend(input_files(input, args)?);
+    }
+    // Checked before the output is created, so a failed run leaves no partial file
+    if !args.output.is_dir() && files.len() > 1 && format.holds_one_report() {
+        return Err(Report::new(Error::SingleReportFormat))
+            .attach_printable(format!("format: {format}, inputs: {}", files.len()));
+    }
+
+    let mut output = if args.output.is_dir() {
         None
     } else {
         let output = args
@@ -110,65 +320,452 @@ fn run(args: Args) -> Result<()> {
             .clone()
             .create()
             .change_context(Error::CreateOutputFile)?;
-        Some(Box::new(output))
+        Some(writer(args, output))
     };
 
-    for input in args.inputs {
-        for file in input
-            .files(has_extension("xml"))
-            .change_context(Error::OpenInputFile)?
-        {
-            let mut file = file.open().change_context(Error::OpenInputFile)?;
-            let json = read_content(&mut file, args.pretty)?;
-
-            // Output is signle file or stdout
-            if let Some(ref mut output) = output {
-                output
-                    .write_all(json.as_bytes())
-                    .change_context(Error::WriteFile)?;
-                output.write_all(b"\n").change_context(Error::WriteFile)?;
-            // Output is dir
-            } else if let Some(name) = file.path().file_name() {
-                let base = args.output.path();
-                // Input is stdin
-                let path = if name == "-" {
-                    base.join(format!(
-                        "n2j-nmap-report-{}.json",
-                        Utc::now().format("%Y-%m-%dT%H-%M-%S").to_string()
-                    ))
-                // Input is dir
-                } else {
-                    let mut path = base.join(name);
-                    path.set_extension("json");
-                    path
-                };
-
-                let mut file = OpenOptions::new()
-                    .write(true)
-                    .create_new(true)
-                    .open(path)
-                    .change_context(Error::CreateOutputFile)?;
-                file.write_all(json.as_bytes())
-                    .change_context(Error::WriteFile)?;
-                file.write_all(b"\n").change_context(Error::WriteFile)?;
+    for file in files {
+        let mut file = file.open().change_context(Error::OpenInputFile)?;
+
+        // Output is signle file or stdout
+        if let Some(ref mut output) = output {
+            convert(&mut file, output.as_mut(), args)?;
+        // Output is dir
+        } else if let Some(name) = file.path().file_name() {
+            let base = args.output.path();
+            // Input is stdin
+            let path = if name == "-" {
+                base.join(format!(
+                    "n2j-nmap-report-{}.{}",
+                    Utc::now().format("%Y-%m-%dT%H-%M-%S"),
+                    format.extension()
+                ))
+            // Input is dir
+            // Reports of `nmap -oA` share the name, so only `.xml` is replaced
+            } else if file.path().extension().is_some_and(|ext| ext == "xml") {
+                let mut path = base.join(name);
+                path.set_extension(format.extension());
+                path
+            } else {
+                let mut name = name.to_os_string();
+                name.push(".");
+                name.push(format.extension());
+                base.join(name)
+            };
+
+            let output = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+                .change_context(Error::CreateOutputFile)?;
+            let mut output = writer(args, output);
+            convert(&mut file, output.as_mut(), args)?;
+            output.finish().change_context(Error::Serialization)?;
+        }
+    }
+
+    match output {
+        Some(mut output) => output.finish().change_context(Error::Serialization),
+        None => Ok(()),
+    }
+}
+
+/// Files of an input path, files named on the command line are read whatever their extension.
+fn input_files(input: &ClioPath, args: &Args) -> Result<Vec<ClioPath>> {
+    input
+        .clone()
+        .files(|path: &ClioPath| {
+            path.path() == input.path()
+                || args
+                    .to
+                    .input_extensions()
+                    .iter()
+                    .any(|ext| has_extension(ext)(path))
+        })
+        .change_context(Error::OpenInputFile)
+}
+
+fn convert(input: impl Read, output: &mut dyn ReportWriter, args: &Args) -> Result<()> {
+    if args.to == Target::Xml {
+        return convert_json(input, output);
+    }
+
+    let mut input = BufReader::new(input);
+    let source = Source::detect(&mut input)?;
+
+    if source == Source::Xml && (args.stream || args.follow) {
+        let (header, hosts): (Header, Box<dyn Iterator<Item = _>>) = if args.follow {
+            let mut tail = Tail::new(input);
+            if let Some(seconds) = args.idle_timeout {
+                tail = tail.timeout(Duration::from_secs(seconds));
+            }
+            let hosts = NmapRun::follow_with(tail).map_err(stream_error)?;
+            (hosts.header().clone(), Box::new(hosts))
+        } else {
+            let hosts = NmapRun::stream(input).map_err(stream_error)?;
+            (hosts.header().clone(), Box::new(hosts))
+        };
+        output
+            .write_header(&header)
+            .change_context(Error::Serialization)?;
+
+        for host in filter_hosts(hosts, args) {
+            let host = host.change_context(Error::Parsing)?;
+            output
+                .write_host(&host)
+                .change_context(Error::Serialization)?;
+            if args.follow {
+                output.flush().change_context(Error::WriteFile)?;
             }
         }
+    } else {
+        let mut report = read_report(input, source, args)?;
+        if let Some(filter) = &args.filter {
+            report = report.filter(filter);
+        }
+        output
+            .write_report(&report)
+            .change_context(Error::Serialization)?;
     }
 
     Ok(())
 }
 
-fn read_content(mut reader: impl Read, pretty: bool) -> Result<String> {
+fn stream_error(report: Report<n2j::Error>) -> Report<Error> {
+    let context = match report.current_context() {
+        n2j::Error::UnsupportedStream => Error::UnsupportedStream,
+        _ => Error::Parsing,
+    };
+    report.change_context(context)
+}
+
+/// Reads a whole report of any input format.
+fn read_report(mut input: impl Read, source: Source, args: &Args) -> Result<NmapRun> {
+    if source == Source::Xml {
+        return read_content(input, args);
+    }
+
+    let mut content = String::new();
+    input
+        .read_to_string(&mut content)
+        .change_context(Error::ReadFile)?;
+
+    match source {
+        Source::Gnmap => NmapRun::parse_gnmap(&content).change_context(Error::GnmapParsing),
+        Source::Masscan => {
+            NmapRun::parse_masscan_json(&content).change_context(Error::MasscanParsing)
+        }
+        Source::Rustscan => {
+            NmapRun::parse_rustscan(&content).change_context(Error::RustscanParsing)
+        }
+        Source::Report => {
+            serde_json::from_str::<NmapRun>(&content).change_context(Error::Deserialization)
+        }
+        _ => NmapRun::parse_normal(&content).change_context(Error::NormalParsing),
+    }
+}
+
+/// Merges all reports of the inputs and writes the merged report.
+fn merge(inputs: &[ClioPath], args: &Args) -> Result<()> {
+    let mut reports = Vec::new();
+
+    for input in inputs {
+        for file in input_files(input, args)? {
+            let mut report = read_file(&file, args)?;
+            SourceReport::record_file(&mut report, file.path().display().to_string());
+            reports.push(report);
+        }
+    }
+
+    let output = args
+        .output
+        .clone()
+        .create()
+        .change_context(Error::CreateOutputFile)?;
+
+    let mut output = writer(args, output);
+    output
+        .write_report(&NmapRun::merge(reports))
+        .change_context(Error::Serialization)?;
+    output.finish().change_context(Error::Serialization)
+}
+
+/// Compares two reports and writes the changes as JSON or as a colored summary.
+fn diff(old: &ClioPath, new: &ClioPath, json: bool, args: &Args) -> Result<()> {
+    let changes = read_file(old, args)?.diff(&read_file(new, args)?);
+
+    let mut output = args
+        .output
+        .clone()
+        .create()
+        .change_context(Error::CreateOutputFile)?;
+
+    if json {
+        if args.pretty {
+            serde_json::to_writer_pretty(&mut output, &changes)
+        } else {
+            serde_json::to_writer(&mut output, &changes)
+        }
+        .change_context(Error::Serialization)?;
+        return output.write_all(b"\n").change_context(Error::WriteFile);
+    }
+
+    let mut summary = Vec::new();
+    write_summary(&changes, &mut summary).change_context(Error::WriteFile)?;
+
+    // Colors only on a terminal and unless NO_COLOR is set, see https://no-color.org
+    let color = output.is_tty() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+    if !color {
+        summary = strip_colors(&summary);
+    }
+
+    output.write_all(&summary).change_context(Error::WriteFile)
+}
+
+/// Removes the ANSI escape sequences written by `cformat!`.
+fn strip_colors(text: &[u8]) -> Vec<u8> {
+    let mut plain = Vec::with_capacity(text.len());
+    let mut bytes = text.iter();
+
+    while let Some(&byte) = bytes.next() {
+        if byte == 0x1b {
+            // `ESC [ parameters final-byte`, the final byte is in `@`..=`~`
+            bytes.by_ref().skip(1).find(|b| (0x40..=0x7e).contains(*b));
+        } else {
+            plain.push(byte);
+        }
+    }
+
+    plain
+}
+
+fn write_summary(changes: &ReportDiff, output: &mut impl Write) -> std::io::Result<()> {
+    if changes.is_empty() {
+        return writeln!(output, "no changes");
+    }
+
+    for host in &changes.hosts {
+        let name = match &host.hostname {
+            Some(hostname) => format!("{} ({hostname})", host.address),
+            None => host.address.to_string(),
+        };
+
+        match host.change {
+            HostChange::Appeared => {
+                writeln!(output, "{}", cformat!("<green,bold>+ {name}</> appeared"))?;
+            }
+            HostChange::Disappeared => {
+                writeln!(output, "{}", cformat!("<red,bold>- {name}</> disappeared"))?;
+            }
+            HostChange::Changed => writeln!(output, "{}", cformat!("<yellow,bold>~ {name}</>"))?,
+        }
+
+        if let Some(os) = &host.os {
+            writeln!(
+                output,
+                "{}",
+                cformat!("    <yellow>~ os</> {}", transition(os))
+            )?;
+        }
+
+        for port in host.ports.iter().flatten() {
+            let id = format!("{}/{}", port.port_number, port.protocol);
+            let service = [&port.service, &port.product, &port.version]
+                .into_iter()
+                .filter_map(|change| change.as_ref()?.new.as_deref())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let line = match port.change {
+                PortChange::Opened if service.is_empty() => cformat!("    <green>+ {id}</> opened"),
+                PortChange::Opened => cformat!("    <green>+ {id}</> opened ({service})"),
+                PortChange::Closed => cformat!("    <red>- {id}</> closed"),
+                PortChange::Changed => {
+                    let state = port.state.as_ref().map(|state| Change {
+                        old: state.old.as_ref().map(ToString::to_string),
+                        new: state.new.as_ref().map(ToString::to_string),
+                    });
+                    let fields = [
+                        ("state", &state),
+                        ("service", &port.service),
+                        ("product", &port.product),
+                        ("version", &port.version),
+                    ];
+                    let details = fields
+                        .into_iter()
+                        .filter_map(|(name, change)| {
+                            Some(format!("{name}: {}", transition(change.as_ref()?)))
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    cformat!("    <yellow>~ {id}</> {details}")
+                }
+            };
+            writeln!(output, "{line}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `old -> new`, a missing value is `-`.
+fn transition(change: &Change<Option<String>>) -> String {
+    let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    format!("{} -> {}", show(&change.old), show(&change.new))
+}
+
+/// Reads a whole report file of any input format.
+fn read_file(file: &ClioPath, args: &Args) -> Result<NmapRun> {
+    let path = file.path().display().to_string();
+    let mut input = BufReader::new(file.clone().open().change_context(Error::OpenInputFile)?);
+    let source = Source::detect(&mut input)?;
+
+    read_report(input, source, args).attach_printable_lazy(|| format!("file: {path}"))
+}
+
+/// Format of an input report.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Source {
+    Xml,
+    Gnmap,
+    Normal,
+    Masscan,
+    Rustscan,
+    /// A JSON report written by n2j
+    Report,
+}
+
+impl Source {
+    /// Guesses the format by the first character: XML starts with `<`, JSON
+    /// with `[` or `{`, text output with a `#` comment, `Host:`, `Starting Nmap`
+    /// or `Nmap scan report`. JSON records with an `ip` first are masscan output,
+    /// other JSON is an n2j report. Text with `Host:` lines is grepable output,
+    /// other text is normal output. Rustscan greppable output has `IP -> [PORTS]` lines.
+    fn detect(input: &mut impl BufRead) -> Result<Self> {
+        let head = input.fill_buf().change_context(Error::ReadFile)?;
+        let first = head
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace() && byte.is_ascii());
+
+        let grepable =
+            head.starts_with(b"Host: ") || head.windows(7).any(|window| window == b"\nHost: ");
+
+        let rustscan = head.windows(5).any(|window| window == b" -> [");
+
+        match first {
+            Some(b'<') | None => Ok(Source::Xml),
+            Some(b'[' | b'{') if is_masscan_json(head) => Ok(Source::Masscan),
+            Some(b'[' | b'{') => Ok(Source::Report),
+            _ if rustscan => Ok(Source::Rustscan),
+            Some(b'#' | b'H' | b'S' | b'N') if grepable => Ok(Source::Gnmap),
+            Some(b'#' | b'H' | b'S' | b'N') => Ok(Source::Normal),
+            _ => Ok(Source::Xml),
+        }
+    }
+}
+
+/// Masscan JSON is a list of records that start with the `ip`, the list of a
+/// scan that found nothing is empty.
+fn is_masscan_json(head: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(head);
+    let head = head.trim_start();
+    let records = head.strip_prefix('[').unwrap_or(head).trim_start();
+
+    records.is_empty()
+        || records.starts_with(']')
+        || records
+            .strip_prefix('{')
+            .is_some_and(|record| record.trim_start().starts_with("\"ip\""))
+}
+
+/// Parses `--where`, the error lists what is wrong with the expression.
+fn parse_filter(text: &str) -> std::result::Result<Expr, String> {
+    text.parse::<Expr>().map_err(|report| {
+        let details = report
+            .frames()
+            .filter_map(|frame| match frame.kind() {
+                FrameKind::Attachment(AttachmentKind::Printable(printable)) => {
+                    Some(printable.to_string())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        format!("{report}: {}", details.join(", "))
+    })
+}
+
+/// Formats of `--format`, nmap XML is only written by `--to xml`.
+fn parse_format(name: &str) -> std::result::Result<Format, String> {
+    match Format::from_str(name) {
+        Ok(Format::Xml) => Err("nmap XML is written with --to xml".to_string()),
+        Ok(format) => Ok(format),
+        Err(_) => {
+            let names = Format::ALL
+                .iter()
+                .filter(|format| **format != Format::Xml)
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            Err(format!("expected one of {}", names.join(", ")))
+        }
+    }
+}
+
+fn parse_column(name: &str) -> std::result::Result<Column, String> {
+    Column::from_str(name).map_err(|_| {
+        let names = Column::ALL.map(|column| column.to_string());
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+/// Drops streamed hosts and ports that don't match `--where`.
+fn filter_hosts<'a>(
+    hosts: impl Iterator<Item = error_stack::Result<Host, n2j::Error>> + 'a,
+    args: &'a Args,
+) -> impl Iterator<Item = error_stack::Result<Host, n2j::Error>> + 'a {
+    hosts.filter_map(|host| match (host, &args.filter) {
+        (Ok(host), Some(filter)) => host.filter(filter).map(Ok),
+        (host, _) => Some(host),
+    })
+}
+
+/// Writes every JSON report of the input (a single document, an array or NDJSON), e.g. as nmap XML.
+fn convert_json(mut input: impl Read, output: &mut dyn ReportWriter) -> Result<()> {
+    let mut content = String::new();
+    input
+        .read_to_string(&mut content)
+        .change_context(Error::ReadFile)?;
+
+    for report in read_json(&content).change_context(Error::Deserialization)? {
+        output
+            .write_report(&report)
+            .change_context(Error::Serialization)?;
+    }
+
+    Ok(())
+}
+
+fn read_content(mut reader: impl Read, args: &Args) -> Result<NmapRun> {
     let mut content = String::new();
     reader
         .read_to_string(&mut content)
         .change_context(Error::ReadFile)?;
-    let report = NmapRun::parse_and_fix(content).change_context(Error::Parsing)?;
 
-    if pretty {
-        serde_json::to_string_pretty(&report)
+    let report = if args.recover {
+        let (report, warnings) = NmapRun::recover(&content).change_context(Error::Parsing)?;
+        for warning in warnings {
+            eprintln!("n2j: warning: {warning}");
+        }
+        report
+    } else if args.lenient {
+        let (report, warnings) =
+            NmapRun::parse_with_options(&content, ParseOptions { strict: false })
+                .change_context(Error::Parsing)?;
+        for warning in warnings {
+            eprintln!("n2j: warning: {warning}");
+        }
+        report
     } else {
-        serde_json::to_string(&report)
-    }
-    .change_context(Error::Serialization)
+        NmapRun::parse_and_fix(content).change_context(Error::Parsing)?
+    };
+
+    Ok(report)
 }
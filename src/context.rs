@@ -0,0 +1,292 @@
+use error_stack::Report;
+use roxmltree::Node;
+use std::str::FromStr;
+
+use crate::{
+    warning::{describe, Location, Warning},
+    Error, Result,
+};
+
+/// Options of [`crate::NmapRun::parse_with_options`].
+#[derive(Clone, Copy, Debug)]
+pub struct ParseOptions {
+    /// Fail on the first problem (default). When disabled, broken elements are
+    /// dropped, unknown enum values are kept as `Other` and every deviation is
+    /// reported as a [`Warning`].
+    pub strict: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions { strict: true }
+    }
+}
+
+/// Enums that keep values unknown to this crate in lenient mode.
+pub(crate) trait OtherVariant {
+    fn other(value: String) -> Self;
+}
+
+/// Parsing state shared by all elements of a report.
+#[derive(Debug, Default)]
+pub struct Context {
+    options: ParseOptions,
+    warnings: Vec<Warning>,
+    /// Location of the parsed fragment in the original report, see [`crate::stream`].
+    origin: Option<Location>,
+}
+
+impl Context {
+    #[must_use]
+    pub fn new(options: ParseOptions) -> Self {
+        Context {
+            options,
+            warnings: Vec::new(),
+            origin: None,
+        }
+    }
+
+    #[must_use]
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    #[must_use]
+    pub fn into_warnings(self) -> Vec<Warning> {
+        self.warnings
+    }
+
+    pub(crate) fn set_origin(&mut self, origin: Option<Location>) {
+        self.origin = origin;
+    }
+
+    /// Location of `node` in the original report.
+    pub(crate) fn locate(&self, node: Node) -> Location {
+        let location = Location::of(node);
+        match &self.origin {
+            Some(origin) => location.within(origin),
+            None => location,
+        }
+    }
+
+    pub(crate) fn warn(&mut self, node: Node, message: String) {
+        let location = self.locate(node);
+        self.warnings.push(Warning::at(node, location, message));
+    }
+
+    /// Parses a child element, in lenient mode a broken element is dropped.
+    ///
+    /// Errors get the [`Location`] of the innermost element that failed.
+    pub(crate) fn child<T>(
+        &mut self,
+        node: Node,
+        parse: impl FnOnce(Node, &mut Context) -> Result<T>,
+    ) -> Result<Option<T>> {
+        match parse(node, self) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if self.options.strict => Err(self.located(e, node)),
+            Err(e) => {
+                self.warn(node, format!("element dropped: {}", describe(&e)));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Same as [`Context::child`] for elements that don't need the context.
+    pub(crate) fn leaf<T>(
+        &mut self,
+        node: Node,
+        parse: impl FnOnce(Node) -> Result<T>,
+    ) -> Result<Option<T>> {
+        self.child(node, |node, _| parse(node))
+    }
+
+    /// An attribute the DTD requires, in lenient mode a missing one is a warning.
+    pub(crate) fn required<'a>(
+        &mut self,
+        node: Node<'a, '_>,
+        attribute: &str,
+    ) -> Result<Option<&'a str>> {
+        match node.attribute(attribute) {
+            Some(value) => Ok(Some(value)),
+            None if self.options.strict => {
+                Err(self.located(Report::new(Error::MissedAttribute), node))
+            }
+            None => {
+                self.warn(node, format!("missing attribute \"{attribute}\""));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Parses an enum attribute value, in lenient mode unknown values become `Other`.
+    pub(crate) fn variant<T>(&mut self, node: Node, attribute: &str, value: &str) -> Result<T>
+    where
+        T: FromStr + OtherVariant,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        match value.parse::<T>() {
+            Ok(value) => Ok(value),
+            Err(e) if self.options.strict => Err(self.located(
+                Report::new(e).change_context(Error::FailedToParseAttribute),
+                node,
+            )),
+            Err(_) => {
+                self.warn(
+                    node,
+                    format!("unknown value \"{value}\" of attribute \"{attribute}\""),
+                );
+                Ok(T::other(value.to_string()))
+            }
+        }
+    }
+
+    /// Converts a syntax error of the parsed document, located in the report
+    /// when the document is a fragment of it.
+    pub(crate) fn syntax_error(&self, e: roxmltree::Error) -> Report<Error> {
+        let pos = e.pos();
+        let location = Location {
+            path: "/".to_string(),
+            line: pos.row,
+            column: pos.col,
+        };
+        let location = match &self.origin {
+            Some(origin) => location.within(origin),
+            None => location,
+        };
+
+        Report::new(e)
+            .change_context(Error::FailedToParseXml)
+            .attach_printable(location)
+    }
+
+    /// Attaches the location of `node` unless a nested element already did.
+    pub(crate) fn located(&self, report: Report<Error>, node: Node) -> Report<Error> {
+        if report.contains::<Location>() {
+            report
+        } else {
+            report.attach_printable(self.locate(node))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        context::ParseOptions,
+        fixture,
+        port::{PortProtocol, PortState, ServiceMethod},
+        runstats::Exit,
+        warning::Location,
+        Error, NmapRun,
+    };
+
+    const XML: &str = r#"<nmaprun scanner="nmap" args="nmap" start="1" version="7.94" xmloutputversion="1.05">
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="22"><state state="open"/><service name="ssh" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="80"><state state="half-open"/><service name="http" method="probed"/></port>
+<port protocol="quic" portid="443"><state state="open"/><service name="https" rpcnum="x"/></port>
+</ports>
+</host>
+</nmaprun>"#;
+
+    #[test]
+    fn strict_fails() {
+        let e = NmapRun::parse_with_options(XML, ParseOptions::default()).unwrap_err();
+        let location = e.downcast_ref::<Location>().unwrap();
+
+        assert_eq!(location.path, "/nmaprun/host[1]/ports/port[2]/state");
+        assert_eq!((location.line, location.column), (5, 34));
+
+        // Streamed hosts are located in the whole report, not in their fragment
+        let e = NmapRun::stream(XML.as_bytes())
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(e.downcast_ref::<Location>(), Some(location));
+    }
+
+    #[test]
+    fn lenient_collects_warnings() {
+        let (report, warnings) =
+            NmapRun::parse_with_options(XML, ParseOptions { strict: false }).unwrap();
+
+        let hosts = report.hosts.unwrap();
+        let ports = hosts[0].ports.as_ref().unwrap();
+
+        assert_eq!(ports.len(), 3);
+        assert_eq!(ports[1].status.state, PortState::Other("half-open".into()));
+        // A missing `conf` is a warning, the service is kept without it
+        let service = ports[1].service.as_ref().unwrap();
+        assert_eq!(service.name, "http");
+        assert_eq!(service.method, Some(ServiceMethod::Probed));
+        assert_eq!(service.confidence_level, None);
+        assert_eq!(ports[2].protocol, PortProtocol::Other("quic".into()));
+        assert!(ports[2].service.is_none());
+
+        let locations = warnings
+            .iter()
+            .map(|w| w.location.as_ref().map(|l| (l.path.as_str(), l.line)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            locations,
+            vec![
+                Some(("/nmaprun/host[1]/ports/port[2]/state", 5)),
+                Some(("/nmaprun/host[1]/ports/port[2]/service", 5)),
+                Some(("/nmaprun/host[1]/ports/port[3]", 6)),
+                Some(("/nmaprun/host[1]/ports/port[3]/service", 6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn strict_requires_conf() {
+        let xml = fixture::host(
+            r#"<ports><port protocol="tcp" portid="80"><state state="open"/><service name="http" method="probed"/></port></ports>"#,
+        );
+        let e = NmapRun::parse(&xml).unwrap_err();
+        assert!(matches!(e.current_context(), Error::MissedAttribute));
+        let location = e.downcast_ref::<Location>().unwrap();
+        assert_eq!(location.path, "/nmaprun/host[1]/ports/port/service");
+    }
+
+    #[test]
+    fn lenient_keeps_unknown_exit() {
+        // Exit values of newer nmap versions are kept in lenient mode only
+        let xml = fixture::run(
+            r#"<runstats><finished time="1" exit="aborted"/><hosts up="0" down="0" total="0"/></runstats>"#,
+        );
+        assert!(NmapRun::parse(&xml).is_err());
+
+        let (report, warnings) =
+            NmapRun::parse_with_options(&xml, ParseOptions { strict: false }).unwrap();
+        let finished = report.runstats.unwrap().finished.unwrap();
+        assert_eq!(finished.exit, Some(Exit::Other("aborted".to_string())));
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn syntax_errors_are_located() {
+        fn location(e: &error_stack::Report<crate::Error>) -> (&str, u32, u32) {
+            let location = e.downcast_ref::<Location>().unwrap();
+            (location.path.as_str(), location.line, location.column)
+        }
+
+        let broken = XML.replace("</ports>", "</prts>");
+        let e = NmapRun::parse(&broken).unwrap_err();
+        assert_eq!(location(&e), ("/", 7, 1));
+
+        let e = NmapRun::stream(broken.as_bytes())
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(location(&e).0, "/nmaprun/host[1]");
+        assert_eq!(location(&e), ("/nmaprun/host[1]", 7, 1));
+
+        let header = XML.replace(r#"start="1""#, r#"start="x""#);
+        let e = NmapRun::stream(header.as_bytes()).err().unwrap();
+        assert_eq!(location(&e), ("/nmaprun", 1, 1));
+    }
+}
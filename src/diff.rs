@@ -0,0 +1,346 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::{
+    host::Host,
+    port::{Port, PortProtocol, PortState, Service},
+    status::HostState,
+    NmapRun,
+};
+
+/// Changes between an older and a newer report, see [`NmapRun::diff`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReportDiff {
+    pub old_start: u32,
+    pub new_start: u32,
+    /// Hosts with changes, in the order of the newer report, then the
+    /// disappeared hosts in the order of the older one.
+    pub hosts: Vec<HostDiff>,
+}
+
+impl ReportDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostDiff {
+    pub address: IpAddr,
+    pub hostname: Option<String>,
+    pub change: HostChange,
+    pub ports: Option<Vec<PortDiff>>,
+    /// Name of the best OS match.
+    pub os: Option<Change<Option<String>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostChange {
+    /// Up in the newer report only.
+    Appeared,
+    /// Up in the older report only.
+    Disappeared,
+    /// Up in both reports.
+    Changed,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PortDiff {
+    pub protocol: PortProtocol,
+    pub port_number: u16,
+    pub change: PortChange,
+    /// `None` on a side that doesn't list the port.
+    pub state: Option<Change<Option<PortState>>>,
+    pub service: Option<Change<Option<String>>>,
+    pub product: Option<Change<Option<String>>>,
+    pub version: Option<Change<Option<String>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortChange {
+    /// Open in the newer report only.
+    Opened,
+    /// Open in the older report only.
+    Closed,
+    /// Another state transition, or a changed service of a port open in both.
+    Changed,
+}
+
+/// A value in the older and in the newer report.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl<T: PartialEq> Change<T> {
+    fn of(old: T, new: T) -> Option<Self> {
+        (old != new).then_some(Change { old, new })
+    }
+}
+
+impl NmapRun {
+    /// Compares the report with a newer one of the same targets.
+    ///
+    /// Hosts are matched by IP address, only hosts that are up count. Ports
+    /// are matched by protocol and number. A port that only one report lists
+    /// is a change only if it is open there: the other report most likely
+    /// counts it in `extraports`.
+    #[must_use]
+    pub fn diff(&self, newer: &NmapRun) -> ReportDiff {
+        let old = up_hosts(self);
+        let new = up_hosts(newer);
+
+        let old_by_ip = old
+            .iter()
+            .map(|(ip, host)| (*ip, *host))
+            .collect::<HashMap<_, _>>();
+        let new_by_ip = new
+            .iter()
+            .map(|(ip, host)| (*ip, *host))
+            .collect::<HashMap<_, _>>();
+
+        let changed = new.iter().filter_map(|(ip, host)| {
+            let old = old_by_ip.get(ip).copied();
+            let change = match old {
+                Some(_) => HostChange::Changed,
+                None => HostChange::Appeared,
+            };
+            host_diff(*ip, change, old, Some(host))
+        });
+
+        let disappeared = old
+            .iter()
+            .filter(|(ip, _)| !new_by_ip.contains_key(ip))
+            .filter_map(|(ip, host)| host_diff(*ip, HostChange::Disappeared, Some(host), None));
+
+        ReportDiff {
+            old_start: self.start,
+            new_start: newer.start,
+            hosts: changed.chain(disappeared).collect(),
+        }
+    }
+}
+
+fn up_hosts(report: &NmapRun) -> Vec<(IpAddr, &Host)> {
+    report
+        .hosts
+        .iter()
+        .flatten()
+        .filter(|host| host.status.state == HostState::Up)
+        .filter_map(|host| Some((host.ip()?, host)))
+        .collect()
+}
+
+fn host_diff(
+    address: IpAddr,
+    change: HostChange,
+    old: Option<&Host>,
+    new: Option<&Host>,
+) -> Option<HostDiff> {
+    let ports = port_diffs(old, new);
+    let os_name = |host: Option<&Host>| {
+        host.and_then(|host| host.os.as_ref())
+            .and_then(|os| os.best_match())
+            .map(|osmatch| osmatch.name.clone())
+    };
+    // A host that disappears keeps the last known OS, not a change of it
+    let os = match change {
+        HostChange::Changed => Change::of(os_name(old), os_name(new)),
+        _ => None,
+    };
+
+    if change == HostChange::Changed && ports.is_empty() && os.is_none() {
+        return None;
+    }
+
+    let hostname = new
+        .or(old)
+        .and_then(|host| host.host_names.as_ref())
+        .and_then(|names| names.first())
+        .map(|name| name.name.clone());
+
+    Some(HostDiff {
+        address,
+        hostname,
+        change,
+        ports: Some(ports).filter(|v| !v.is_empty()),
+        os,
+    })
+}
+
+fn port_diffs(old: Option<&Host>, new: Option<&Host>) -> Vec<PortDiff> {
+    let (old, new) = (ports(old), ports(new));
+
+    let keys = new
+        .iter()
+        .chain(old.iter().filter(|port| find(new, port).is_none()));
+
+    keys.filter_map(|key| port_diff(find(old, key), find(new, key)))
+        .collect()
+}
+
+fn ports(host: Option<&Host>) -> &[Port] {
+    host.and_then(|host| host.ports.as_deref()).unwrap_or(&[])
+}
+
+fn find<'a>(ports: &'a [Port], key: &Port) -> Option<&'a Port> {
+    ports
+        .iter()
+        .find(|port| port.protocol == key.protocol && port.port_number == key.port_number)
+}
+
+fn port_diff(old: Option<&Port>, new: Option<&Port>) -> Option<PortDiff> {
+    let key = new.or(old)?;
+    let state = |port: Option<&Port>| port.map(|port| port.status.state.clone());
+    let is_open = |port: Option<&Port>| port.is_some_and(Port::is_open);
+
+    let change = match (is_open(old), is_open(new)) {
+        (false, true) => PortChange::Opened,
+        (true, false) => PortChange::Closed,
+        _ if old.is_none() || new.is_none() => return None,
+        _ => PortChange::Changed,
+    };
+
+    let old_service = old.and_then(|port| port.service.as_ref());
+    let new_service = new.and_then(|port| port.service.as_ref());
+    let field = |get: fn(&Service) -> Option<String>| {
+        Change::of(old_service.and_then(get), new_service.and_then(get))
+    };
+
+    let diff = PortDiff {
+        protocol: key.protocol.clone(),
+        port_number: key.port_number,
+        change,
+        state: Change::of(state(old), state(new)),
+        service: field(|service| Some(service.name.clone())),
+        product: field(|service| service.product.clone()),
+        version: field(|service| service.version.clone()),
+    };
+
+    let changed = diff.state.is_some()
+        || diff.service.is_some()
+        || diff.product.is_some()
+        || diff.version.is_some();
+
+    changed.then_some(diff)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        diff::{HostChange, PortChange},
+        fixture,
+        port::PortState,
+        NmapRun,
+    };
+
+    const OLD: &str = r#"<nmaprun scanner="nmap" args="nmap -sV -O 10.0.0.0/29" start="100" version="7.94" xmloutputversion="1.05">
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="22"><state state="open"/><service name="ssh" product="OpenSSH" version="8.9" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="23"><state state="open"/><service name="telnet" method="table" conf="3"/></port>
+<port protocol="tcp" portid="443"><state state="filtered"/></port>
+</ports>
+<os><osmatch name="Linux 5.0 - 5.14" accuracy="98" line="1"/></os>
+</host>
+<host><status state="up"/><address addr="10.0.0.2" addrtype="ipv4"/>
+<ports><port protocol="tcp" portid="80"><state state="open"/></port></ports>
+</host>
+<host><status state="up"/><address addr="10.0.0.4" addrtype="ipv4"/></host>
+</nmaprun>"#;
+
+    const NEW: &str = r#"<nmaprun scanner="nmap" args="nmap -sV -O 10.0.0.0/29" start="200" version="7.94" xmloutputversion="1.05">
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="22"><state state="open"/><service name="ssh" product="OpenSSH" version="9.6" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="443"><state state="open"/><service name="https" method="table" conf="3"/></port>
+</ports>
+<os><osmatch name="Linux 5.0 - 5.14" accuracy="98" line="1"/></os>
+</host>
+<host><status state="up"/><address addr="10.0.0.3" addrtype="ipv4"/>
+<ports><port protocol="tcp" portid="8080"><state state="open"/></port></ports>
+</host>
+<host><status state="up"/><address addr="10.0.0.4" addrtype="ipv4"/></host>
+</nmaprun>"#;
+
+    #[test]
+    fn diff() {
+        let old = NmapRun::parse(OLD).unwrap();
+        let new = NmapRun::parse(NEW).unwrap();
+        let diff = old.diff(&new);
+
+        let hosts = diff
+            .hosts
+            .iter()
+            .map(|host| (host.address.to_string(), host.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hosts,
+            vec![
+                ("10.0.0.1".to_string(), HostChange::Changed),
+                ("10.0.0.3".to_string(), HostChange::Appeared),
+                ("10.0.0.2".to_string(), HostChange::Disappeared),
+            ]
+        );
+
+        let ports = diff.hosts[0].ports.as_ref().unwrap();
+        let changes = ports
+            .iter()
+            .map(|port| (port.port_number, port.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (22, PortChange::Changed),
+                (443, PortChange::Opened),
+                (23, PortChange::Closed),
+            ]
+        );
+
+        let ssh = ports[0].version.as_ref().unwrap();
+        assert_eq!(
+            (ssh.old.as_deref(), ssh.new.as_deref()),
+            (Some("8.9"), Some("9.6"))
+        );
+        let https = ports[1].state.as_ref().unwrap();
+        assert_eq!(
+            (&https.old, &https.new),
+            (&Some(PortState::Filtered), &Some(PortState::Open))
+        );
+        assert!(diff.hosts[0].os.is_none());
+
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn open_filtered_is_open() {
+        let report = |dns: &str, snmp: &str| {
+            NmapRun::parse(&fixture::host(&format!(
+                r#"<ports><port protocol="udp" portid="53"><state state="{dns}"/></port><port protocol="udp" portid="161"><state state="{snmp}"/></port></ports>"#
+            )))
+            .unwrap()
+        };
+        let old = report("open|filtered", "open|filtered");
+        let new = report("closed", "open");
+
+        let diff = old.diff(&new);
+        let changes = diff.hosts[0]
+            .ports
+            .iter()
+            .flatten()
+            .map(|port| (port.port_number, port.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![(53, PortChange::Closed), (161, PortChange::Changed)]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use error_stack::ResultExt;
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::{Attribute, Error, Result};
+
+/// Run metadata recorded by nmap between hosts, in report order.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Event {
+    Verbose(Level),
+    Debugging(Level),
+    TaskBegin(Task),
+    TaskProgress(TaskProgress),
+    TaskEnd(Task),
+}
+
+impl Event {
+    /// Parses a run-level node, returns `None` if the node is not an event.
+    pub fn parse(node: Node) -> Result<Option<Self>> {
+        let event = match node.tag_name().name() {
+            "verbose" => Event::Verbose(Level::parse(node, "verbose")?),
+            "debugging" => Event::Debugging(Level::parse(node, "debugging")?),
+            "taskbegin" => Event::TaskBegin(Task::parse(node, "taskbegin")?),
+            "taskprogress" => Event::TaskProgress(TaskProgress::parse(node)?),
+            "taskend" => Event::TaskEnd(Task::parse(node, "taskend")?),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(event))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Level {
+    pub level: u32,
+}
+
+impl Level {
+    fn parse(node: Node, parent: &'static str) -> Result<Self> {
+        let level = node
+            .attribute("level")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("level", parent)))
+            .map(str::parse::<u32>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("level", parent)))?;
+
+        Ok(Level { level })
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Task {
+    pub task: String,
+    pub time: i64,
+    pub extrainfo: Option<String>,
+}
+
+impl Task {
+    fn parse(node: Node, parent: &'static str) -> Result<Self> {
+        let task = node
+            .attribute("task")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("task", parent)))?
+            .to_string();
+
+        let time = node
+            .attribute("time")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("time", parent)))
+            .map(str::parse::<i64>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("time", parent)))?;
+
+        let extrainfo = node.attribute("extrainfo").map(Into::into);
+
+        Ok(Task {
+            task,
+            time,
+            extrainfo,
+        })
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TaskProgress {
+    pub task: String,
+    pub time: i64,
+    pub percent: f64,
+    pub remaining: Option<u64>,
+    pub etc: Option<i64>,
+}
+
+impl TaskProgress {
+    fn parse(node: Node) -> Result<Self> {
+        let task = node
+            .attribute("task")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("task", "taskprogress")))?
+            .to_string();
+
+        let time = node
+            .attribute("time")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("time", "taskprogress")))
+            .map(str::parse::<i64>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("time", "taskprogress")))?;
+
+        let percent = node
+            .attribute("percent")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("percent", "taskprogress")))
+            .map(str::parse::<f64>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("percent", "taskprogress")))?;
+
+        let remaining = node
+            .attribute("remaining")
+            .map(str::parse::<u64>)
+            .transpose()
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("remaining", "taskprogress")))?;
+
+        let etc = node
+            .attribute("etc")
+            .map(str::parse::<i64>)
+            .transpose()
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("etc", "taskprogress")))?;
+
+        Ok(TaskProgress {
+            task,
+            time,
+            percent,
+            remaining,
+            etc,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{event::Event, fixture, NmapRun};
+
+    const EVENTS: &str = r#"<verbose level="1"/>
+<debugging level="2"/>
+<taskbegin task="SYN Stealth Scan" time="1700000001"/>
+<taskprogress task="SYN Stealth Scan" time="1700000031" percent="42.50" remaining="40" etc="1700000071"/>
+<taskend task="SYN Stealth Scan" time="1700000070" extrainfo="1000 total ports"/>
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/></host>"#;
+
+    #[test]
+    fn events() {
+        let events = NmapRun::parse(&fixture::run(EVENTS))
+            .unwrap()
+            .events
+            .unwrap();
+        assert_eq!(events.len(), 5);
+
+        assert!(matches!(&events[0], Event::Verbose(level) if level.level == 1));
+        assert!(matches!(&events[1], Event::Debugging(level) if level.level == 2));
+        assert!(matches!(&events[2], Event::TaskBegin(task) if task.extrainfo.is_none()));
+        let Event::TaskProgress(progress) = &events[3] else {
+            panic!("expected task progress, got {:?}", events[3]);
+        };
+        assert!((progress.percent - 42.5).abs() < f64::EPSILON);
+        assert_eq!(
+            (progress.remaining, progress.etc),
+            (Some(40), Some(1_700_000_071))
+        );
+
+        let end = serde_json::to_value(&events[4]).unwrap();
+        assert_eq!(
+            end,
+            serde_json::json!({
+                "type": "taskend",
+                "task": "SYN Stealth Scan",
+                "time": 1_700_000_070,
+                "extrainfo": "1000 total ports"
+            })
+        );
+    }
+}
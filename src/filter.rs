@@ -0,0 +1,621 @@
+use error_stack::{Report, ResultExt};
+use regex::Regex;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::{address::Address, host::Host, os::OsMatch, port::Port, Error, NmapRun, Result};
+
+/// A filter over hosts and ports, parsed from expressions like
+/// `port = 445 and protocol = tcp and state = open`.
+///
+/// Fields:
+/// - host: `ip`, `mac`, `hostname`, `status`, `os` (name of the best OS
+///   match), `os.accuracy`, `distance`
+/// - port: `port`, `protocol`, `state`, `reason`, `service` (or `service.name`),
+///   `product`, `version`, `extrainfo`, `tunnel`, `script` (ids of the port
+///   and host scripts)
+///
+/// Operators: `=`, `!=`, `<`, `<=`, `>`, `>=` (numbers), `contains`,
+/// `matches` (a regex), `in` (CIDR ranges for `ip`, port ranges like
+/// `1-1024,8080` for numbers, a comma separated list otherwise), combined with
+/// `and`, `or`, `not` and parentheses. Text comparisons ignore case, except
+/// regexes. Values with spaces or operator characters are quoted with `"` or `'`.
+#[derive(Clone, Debug)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Test),
+}
+
+/// Comparison of a field with a value, the leaf of an [`Expr`].
+#[derive(Clone, Debug)]
+pub struct Test {
+    field: Field,
+    op: Op,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Ip,
+    Mac,
+    Hostname,
+    Status,
+    Os,
+    OsAccuracy,
+    Distance,
+    Port,
+    Protocol,
+    State,
+    Reason,
+    Service,
+    Product,
+    Version,
+    ExtraInfo,
+    Tunnel,
+    Script,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Ip,
+    Number,
+    Text,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Eq(Operand),
+    Ne(Operand),
+    Lt(f64),
+    Le(f64),
+    Gt(f64),
+    Ge(f64),
+    Contains(String),
+    Matches(Regex),
+    In(Vec<Operand>),
+}
+
+#[derive(Clone, Debug)]
+enum Operand {
+    Ip(IpAddr),
+    Cidr(IpAddr, u8),
+    Number(f64),
+    Range(f64, f64),
+    Text(String),
+}
+
+/// A value of a field of a host or port.
+enum Value {
+    Ip(IpAddr),
+    Number(f64),
+    Text(String),
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "ip" | "address" => Field::Ip,
+            "mac" => Field::Mac,
+            "hostname" => Field::Hostname,
+            "status" => Field::Status,
+            "os" | "os.name" => Field::Os,
+            "os.accuracy" => Field::OsAccuracy,
+            "distance" => Field::Distance,
+            "port" => Field::Port,
+            "protocol" => Field::Protocol,
+            "state" => Field::State,
+            "reason" => Field::Reason,
+            "service" | "service.name" => Field::Service,
+            "product" | "service.product" => Field::Product,
+            "version" | "service.version" => Field::Version,
+            "extrainfo" | "service.extrainfo" => Field::ExtraInfo,
+            "tunnel" | "service.tunnel" => Field::Tunnel,
+            "script" => Field::Script,
+            _ => return None,
+        })
+    }
+
+    fn kind(self) -> Kind {
+        match self {
+            Field::Ip => Kind::Ip,
+            Field::OsAccuracy | Field::Distance | Field::Port => Kind::Number,
+            _ => Kind::Text,
+        }
+    }
+
+    fn is_port(self) -> bool {
+        matches!(
+            self,
+            Field::Port
+                | Field::Protocol
+                | Field::State
+                | Field::Reason
+                | Field::Service
+                | Field::Product
+                | Field::Version
+                | Field::ExtraInfo
+                | Field::Tunnel
+                | Field::Script
+        )
+    }
+
+    fn values(self, host: &Host, port: Option<&Port>) -> Vec<Value> {
+        let text = |value: Option<String>| value.map(Value::Text).into_iter().collect();
+        let service = port.and_then(|port| port.service.as_ref());
+
+        match self {
+            Field::Ip => host
+                .addresses
+                .iter()
+                .filter_map(|address| match address {
+                    Address::IpAddr(ip) => Some(Value::Ip(*ip)),
+                    Address::MacAddr { .. } => None,
+                })
+                .collect(),
+            Field::Mac => host
+                .addresses
+                .iter()
+                .filter_map(|address| match address {
+                    Address::MacAddr { addr, .. } => Some(Value::Text(addr.to_string())),
+                    Address::IpAddr(_) => None,
+                })
+                .collect(),
+            Field::Hostname => host
+                .host_names
+                .iter()
+                .flatten()
+                .map(|name| Value::Text(name.name.clone()))
+                .collect(),
+            Field::Status => text(Some(host.status.state.to_string())),
+            Field::Os => text(best_match(host).map(|osmatch| osmatch.name.clone())),
+            Field::OsAccuracy => best_match(host)
+                .and_then(|osmatch| osmatch.accuracy)
+                .map(|accuracy| Value::Number(f64::from(accuracy)))
+                .into_iter()
+                .collect(),
+            Field::Distance => host
+                .distance
+                .as_ref()
+                .map(|distance| Value::Number(f64::from(distance.value)))
+                .into_iter()
+                .collect(),
+            Field::Port => port
+                .map(|port| Value::Number(f64::from(port.port_number)))
+                .into_iter()
+                .collect(),
+            Field::Protocol => text(port.map(|port| port.protocol.to_string())),
+            Field::State => text(port.map(|port| port.status.state.to_string())),
+            Field::Reason => text(port.and_then(|port| port.status.reason.clone())),
+            Field::Service => text(service.map(|service| service.name.clone())),
+            Field::Product => text(service.and_then(|service| service.product.clone())),
+            Field::Version => text(service.and_then(|service| service.version.clone())),
+            Field::ExtraInfo => text(service.and_then(|service| service.extra_info.clone())),
+            Field::Tunnel => {
+                text(service.and_then(|service| Some(service.tunnel.as_ref()?.to_string())))
+            }
+            Field::Script => port
+                .and_then(|port| port.scripts.as_ref())
+                .into_iter()
+                .chain(host.host_scripts.as_ref())
+                .flatten()
+                .map(|script| Value::Text(script.id.clone()))
+                .collect(),
+        }
+    }
+}
+
+fn best_match(host: &Host) -> Option<&OsMatch> {
+    host.os.as_ref()?.best_match()
+}
+
+impl Operand {
+    fn parse(kind: Kind, value: &str) -> Option<Self> {
+        match kind {
+            Kind::Ip => match value.split_once('/') {
+                Some((ip, prefix)) => {
+                    let ip = ip.parse::<IpAddr>().ok()?;
+                    let prefix = prefix.parse::<u8>().ok()?;
+                    let max = if ip.is_ipv4() { 32 } else { 128 };
+                    (prefix <= max).then_some(Operand::Cidr(ip, prefix))
+                }
+                None => value.parse().ok().map(Operand::Ip),
+            },
+            Kind::Number => match value.split_once('-') {
+                Some((first, last)) => {
+                    Some(Operand::Range(first.parse().ok()?, last.parse().ok()?))
+                }
+                None => value.parse().ok().map(Operand::Number),
+            },
+            Kind::Text => Some(Operand::Text(value.to_lowercase())),
+        }
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Operand::Ip(ip), Value::Ip(value)) => ip == value,
+            (Operand::Cidr(network, prefix), Value::Ip(value)) => {
+                in_network(*network, *prefix, *value)
+            }
+            #[allow(clippy::float_cmp)]
+            (Operand::Number(number), Value::Number(value)) => number == value,
+            (Operand::Range(first, last), Value::Number(value)) => first <= value && value <= last,
+            (Operand::Text(text), Value::Text(value)) => *text == value.to_lowercase(),
+            _ => false,
+        }
+    }
+}
+
+fn in_network(network: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+impl Test {
+    fn matches(&self, host: &Host, port: Option<&Port>) -> bool {
+        let values = self.field.values(host, port);
+        let any = |test: &dyn Fn(&Value) -> bool| values.iter().any(test);
+        let number = |test: &dyn Fn(f64) -> bool| {
+            any(&|value| matches!(value, Value::Number(value) if test(*value)))
+        };
+
+        match &self.op {
+            Op::Eq(operand) => any(&|value| operand.matches(value)),
+            Op::Ne(operand) => !any(&|value| operand.matches(value)),
+            Op::Lt(limit) => number(&|value| value < *limit),
+            Op::Le(limit) => number(&|value| value <= *limit),
+            Op::Gt(limit) => number(&|value| value > *limit),
+            Op::Ge(limit) => number(&|value| value >= *limit),
+            Op::Contains(text) => any(&|value| match value {
+                Value::Text(value) => value.to_lowercase().contains(text.as_str()),
+                _ => false,
+            }),
+            Op::Matches(regex) => any(&|value| match value {
+                Value::Text(value) => regex.is_match(value),
+                Value::Ip(ip) => regex.is_match(&ip.to_string()),
+                Value::Number(number) => regex.is_match(&number.to_string()),
+            }),
+            Op::In(operands) => any(&|value| operands.iter().any(|operand| operand.matches(value))),
+        }
+    }
+}
+
+impl Expr {
+    /// Whether the expression tests fields of ports, see [`Host::filter`].
+    fn has_port_fields(&self) -> bool {
+        match self {
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.has_port_fields() || right.has_port_fields()
+            }
+            Expr::Not(expr) => expr.has_port_fields(),
+            Expr::Test(test) => test.field.is_port(),
+        }
+    }
+
+    fn matches(&self, host: &Host, port: Option<&Port>) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(host, port) && right.matches(host, port),
+            Expr::Or(left, right) => left.matches(host, port) || right.matches(host, port),
+            Expr::Not(expr) => !expr.matches(host, port),
+            Expr::Test(test) => test.matches(host, port),
+        }
+    }
+}
+
+impl Host {
+    /// The host with only the ports that match `expr`, `None` if nothing matches.
+    ///
+    /// An expression without port fields keeps or drops the whole host. With
+    /// port fields every port is tested together with its host and the host is
+    /// kept if a port matches. A host without ports is tested alone.
+    #[must_use]
+    pub fn filter(mut self, expr: &Expr) -> Option<Host> {
+        let has_ports = self.ports.as_ref().is_some_and(|ports| !ports.is_empty());
+
+        if !expr.has_port_fields() || !has_ports {
+            return expr.matches(&self, None).then_some(self);
+        }
+
+        let ports = self
+            .ports
+            .take()
+            .into_iter()
+            .flatten()
+            .filter(|port| expr.matches(&self, Some(port)))
+            .collect::<Vec<_>>();
+
+        (!ports.is_empty()).then_some(Host {
+            ports: Some(ports),
+            ..self
+        })
+    }
+}
+
+impl NmapRun {
+    /// The report with only the hosts and ports that match `expr`, see
+    /// [`Host::filter`]. Run data such as `runstats` is kept as is.
+    #[must_use]
+    pub fn filter(self, expr: &Expr) -> NmapRun {
+        let hosts = self
+            .hosts
+            .into_iter()
+            .flatten()
+            .filter_map(|host| host.filter(expr))
+            .collect::<Vec<_>>();
+
+        NmapRun {
+            hosts: Some(hosts).filter(|v| !v.is_empty()),
+            ..self
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = Report<Error>;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expr = parser.or()?;
+
+        match parser.tokens.get(parser.position) {
+            None => Ok(expr),
+            Some(token) => Err(unexpected(token)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(&'static str),
+    Open,
+    Close,
+}
+
+const SYMBOLS: [&str; 8] = ["==", "!=", "<=", ">=", "=", "<", ">", "~"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            rest = &rest[1..];
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or(Error::FailedToParseFilter)
+                .attach_printable_lazy(|| format!("unclosed quote: {rest}"))?;
+            tokens.push(Token::Quoted(rest[1..=end].to_string()));
+            rest = &rest[end + 2..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "()\"'=!<>~".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(Report::new(Error::FailedToParseFilter))
+                    .attach_printable(format!("unexpected character: {c}"));
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+fn unexpected(token: &Token) -> Report<Error> {
+    Report::new(Error::FailedToParseFilter).attach_printable(format!("unexpected: {token:?}"))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or(Error::FailedToParseFilter)
+            .attach_printable("unexpected end of the expression")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(
+            self.tokens.get(self.position),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword)
+        );
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+
+        match self.next()? {
+            Token::Open => {
+                let expr = self.or()?;
+                match self.next()? {
+                    Token::Close => Ok(expr),
+                    token => Err(unexpected(&token)),
+                }
+            }
+            Token::Word(name) => self.test(&name).map(Expr::Test),
+            token => Err(unexpected(&token)),
+        }
+    }
+
+    fn test(&mut self, name: &str) -> Result<Test> {
+        let field = Field::parse(&name.to_lowercase())
+            .ok_or(Error::FailedToParseFilter)
+            .attach_printable_lazy(|| format!("unknown field: {name}"))?;
+        let kind = field.kind();
+
+        let op = match self.next()? {
+            Token::Symbol(symbol) => symbol,
+            Token::Word(word) => match word.to_lowercase().as_str() {
+                "contains" => "contains",
+                "matches" => "~",
+                "in" => "in",
+                _ => return Err(unexpected(&Token::Word(word))),
+            },
+            token => return Err(unexpected(&token)),
+        };
+
+        let value = match self.next()? {
+            Token::Word(value) | Token::Quoted(value) => value,
+            token => return Err(unexpected(&token)),
+        };
+
+        let invalid = || {
+            Report::new(Error::FailedToParseFilter)
+                .attach_printable(format!("invalid value for {name} {op}: {value}"))
+        };
+        let operand = || Operand::parse(kind, &value).ok_or_else(invalid);
+        let number = || {
+            if kind == Kind::Number {
+                value.parse::<f64>().map_err(|_| invalid())
+            } else {
+                Err(invalid())
+            }
+        };
+
+        let op = match op {
+            "=" | "==" => Op::Eq(operand()?),
+            "!=" => Op::Ne(operand()?),
+            "<" => Op::Lt(number()?),
+            "<=" => Op::Le(number()?),
+            ">" => Op::Gt(number()?),
+            ">=" => Op::Ge(number()?),
+            "contains" => Op::Contains(value.to_lowercase()),
+            "~" => Op::Matches(
+                Regex::new(&value)
+                    .change_context(Error::FailedToParseFilter)
+                    .attach_printable_lazy(|| format!("invalid regex: {value}"))?,
+            ),
+            _ => Op::In(
+                value
+                    .split(',')
+                    .map(|item| Operand::parse(kind, item.trim()).ok_or_else(invalid))
+                    .collect::<Result<_>>()?,
+            ),
+        };
+
+        Ok(Test { field, op })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{filter::Expr, NmapRun};
+
+    const XML: &str = r#"<nmaprun scanner="nmap" args="nmap -sV -O 10.0.0.0/24" start="1" version="7.94" xmloutputversion="1.05">
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="80"><state state="open"/><service name="http" product="Apache httpd" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="445"><state state="open"/><service name="microsoft-ds" method="table" conf="3"/></port>
+</ports>
+<os><osmatch name="Microsoft Windows 10" accuracy="96" line="1"/></os>
+</host>
+<host><status state="up"/><address addr="192.168.1.5" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="8080"><state state="open"/><service name="http" product="nginx" method="probed" conf="10"/></port>
+<port protocol="udp" portid="445"><state state="closed"/></port>
+</ports>
+<os><osmatch name="Linux 5.4" accuracy="85" line="2"/></os>
+</host>
+</nmaprun>"#;
+
+    fn addresses(report: &NmapRun, filter: &str) -> Vec<(String, Vec<u16>)> {
+        let expr = filter.parse::<Expr>().unwrap();
+        report
+            .clone()
+            .filter(&expr)
+            .hosts
+            .into_iter()
+            .flatten()
+            .map(|host| {
+                let ports = host.ports.iter().flatten().map(|p| p.port_number).collect();
+                (host.ip().unwrap().to_string(), ports)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn filter() {
+        let report = NmapRun::parse(XML).unwrap();
+        let first = || ("10.0.0.1".to_string(), vec![445]);
+
+        assert_eq!(
+            addresses(&report, "port = 445 and protocol = tcp and state = open"),
+            vec![first()]
+        );
+        assert_eq!(
+            addresses(&report, r#"service = http and product contains "apache""#),
+            vec![("10.0.0.1".to_string(), vec![80])]
+        );
+        assert_eq!(addresses(&report, "os.accuracy >= 90").len(), 1);
+        assert_eq!(
+            addresses(&report, "ip in 192.168.0.0/16 and not port in 1-1024"),
+            vec![("192.168.1.5".to_string(), vec![8080])]
+        );
+        assert_eq!(
+            addresses(&report, "(os ~ '^Linux' or ip = 10.0.0.1) and port in 445"),
+            vec![first(), ("192.168.1.5".to_string(), vec![445])]
+        );
+
+        for invalid in [
+            "port >",
+            "ports = 1",
+            "port < http",
+            "(ip = 10.0.0.1",
+            "ip in 10.0.0.0/33",
+        ] {
+            assert!(invalid.parse::<Expr>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Stub reports for the tests of element parsers.
+
+/// A report holding `body`, e.g. `<host>` or `<runstats>` elements.
+pub(crate) fn run(body: &str) -> String {
+    format!(
+        r#"<nmaprun scanner="nmap" args="nmap 10.0.0.1" start="1" version="7.94" xmloutputversion="1.05">
+{body}
+</nmaprun>"#
+    )
+}
+
+/// A report of the up host 10.0.0.1 holding `body`, e.g. `<ports>` or `<os>`.
+pub(crate) fn host(body: &str) -> String {
+    run(&format!(
+        r#"<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>{body}</host>"#
+    ))
+}
@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::net::IpAddr;
+
+use crate::{
+    address::Address,
+    host::Host,
+    hostname::Hostname,
+    port::{Port, PortProtocol},
+    scaninfo::ScanInfo,
+    stream::Header,
+    NmapRun,
+};
+
+/// Run data copied into every flattened document.
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunContext {
+    pub scanner: String,
+    pub args: String,
+    pub start: u32,
+    pub version: String,
+    /// Types of the `<scaninfo>` elements, e.g. `syn`, for a port only the
+    /// types that scanned its protocol.
+    pub scan_types: Option<Vec<String>>,
+}
+
+/// Who a port belongs to.
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostIdentity {
+    /// First IP address, a single field to index hosts by.
+    pub ip: Option<IpAddr>,
+    pub addresses: Vec<Address>,
+    pub host_names: Option<Vec<Hostname>>,
+}
+
+/// A host with its first IP address, and the context of its run when made by a [`Flattener`].
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostDocument {
+    pub run: Option<RunContext>,
+    pub ip: Option<IpAddr>,
+    #[serde(flatten)]
+    pub host: Host,
+}
+
+impl HostDocument {
+    #[must_use]
+    pub fn new(host: &Host) -> Self {
+        HostDocument {
+            run: None,
+            ip: host.ip(),
+            host: host.clone(),
+        }
+    }
+}
+
+/// An open port with its host, and the context of its run when made by a [`Flattener`].
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PortDocument {
+    pub run: Option<RunContext>,
+    pub host: HostIdentity,
+    #[serde(flatten)]
+    pub port: Port,
+}
+
+impl PortDocument {
+    /// Documents of the open (`open` or `open|filtered`) ports of the host.
+    #[must_use]
+    pub fn of_host(host: &Host) -> Vec<PortDocument> {
+        let identity = HostIdentity {
+            ip: host.ip(),
+            addresses: host.addresses.clone(),
+            host_names: host.host_names.clone(),
+        };
+
+        host.ports
+            .iter()
+            .flatten()
+            .filter(|port| port.is_open())
+            .map(|port| PortDocument {
+                run: None,
+                host: identity.clone(),
+                port: port.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Makes self-contained documents of the hosts of a report, e.g. to index
+/// them in a log store one by one.
+#[derive(Clone, Debug)]
+pub struct Flattener {
+    run: RunContext,
+    scaninfos: Vec<(PortProtocol, String)>,
+}
+
+impl Flattener {
+    #[must_use]
+    pub fn new(report: &NmapRun) -> Self {
+        Self::with(
+            &report.scanner,
+            &report.args,
+            report.start,
+            &report.version,
+            report.scaninfos.as_deref(),
+        )
+    }
+
+    /// A flattener of the hosts of [`crate::stream::HostStream`].
+    #[must_use]
+    pub fn of_header(header: &Header) -> Self {
+        Self::with(
+            &header.scanner,
+            &header.args,
+            header.start,
+            &header.version,
+            header.scaninfos.as_deref(),
+        )
+    }
+
+    fn with(
+        scanner: &str,
+        args: &str,
+        start: u32,
+        version: &str,
+        scaninfos: Option<&[ScanInfo]>,
+    ) -> Self {
+        let scaninfos = scaninfos
+            .unwrap_or_default()
+            .iter()
+            .map(|scaninfo| (scaninfo.protocol.clone(), scaninfo.ttype.clone()))
+            .collect::<Vec<_>>();
+
+        let run = RunContext {
+            scanner: scanner.to_string(),
+            args: args.to_string(),
+            start,
+            version: version.to_string(),
+            scan_types: scan_types(scaninfos.iter().map(|(_, ttype)| ttype)),
+        };
+
+        Flattener { run, scaninfos }
+    }
+
+    #[must_use]
+    pub fn host(&self, host: &Host) -> HostDocument {
+        HostDocument {
+            run: Some(self.run.clone()),
+            ..HostDocument::new(host)
+        }
+    }
+
+    /// [`PortDocument::of_host`] with the run context.
+    #[must_use]
+    pub fn ports(&self, host: &Host) -> Vec<PortDocument> {
+        let mut documents = PortDocument::of_host(host);
+        for document in &mut documents {
+            let types = self
+                .scaninfos
+                .iter()
+                .filter(|(protocol, _)| *protocol == document.port.protocol)
+                .map(|(_, ttype)| ttype);
+
+            document.run = Some(RunContext {
+                scan_types: scan_types(types),
+                ..self.run.clone()
+            });
+        }
+        documents
+    }
+}
+
+fn scan_types<'a>(types: impl Iterator<Item = &'a String>) -> Option<Vec<String>> {
+    Some(types.cloned().collect::<Vec<_>>()).filter(|v| !v.is_empty())
+}
+
+impl NmapRun {
+    /// A document per host, see [`Flattener`].
+    #[must_use]
+    pub fn host_documents(&self) -> Vec<HostDocument> {
+        let flattener = Flattener::new(self);
+        self.hosts
+            .iter()
+            .flatten()
+            .map(|host| flattener.host(host))
+            .collect()
+    }
+
+    /// A document per open port, see [`Flattener`].
+    #[must_use]
+    pub fn port_documents(&self) -> Vec<PortDocument> {
+        let flattener = Flattener::new(self);
+        self.hosts
+            .iter()
+            .flatten()
+            .flat_map(|host| flattener.ports(host))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::NmapRun;
+
+    const XML: &str = r#"<nmaprun scanner="nmap" args="nmap -sS -sU -p T:22,80,U:53 10.0.0.1" start="1700000000" version="7.94" xmloutputversion="1.05">
+<scaninfo type="syn" protocol="tcp" numservices="2" services="22,80"/>
+<scaninfo type="udp" protocol="udp" numservices="1" services="53"/>
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<hostnames><hostname name="ns.lan" type="PTR"/></hostnames>
+<ports>
+<port protocol="tcp" portid="22"><state state="open"/></port>
+<port protocol="tcp" portid="80"><state state="closed"/></port>
+<port protocol="udp" portid="53"><state state="open|filtered"/></port>
+</ports>
+</host>
+</nmaprun>"#;
+
+    #[test]
+    fn flatten() {
+        let report = NmapRun::parse(XML).unwrap();
+
+        let hosts = report.host_documents();
+        assert_eq!(hosts.len(), 1);
+        let host = serde_json::to_value(&hosts[0]).unwrap();
+        assert_eq!(host["run"]["args"], report.args);
+        assert_eq!(host["run"]["scan_types"], serde_json::json!(["syn", "udp"]));
+        assert_eq!(host["ip"], "10.0.0.1");
+        assert_eq!(host["status"]["state"], "up");
+
+        let ports = report.port_documents();
+        let ports = ports
+            .iter()
+            .map(|port| serde_json::to_value(port).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ports.len(), 2);
+        assert_eq!(ports[0]["port_number"], 22);
+        assert_eq!(ports[0]["run"]["scan_types"], serde_json::json!(["syn"]));
+        assert_eq!(ports[0]["run"]["start"], 1_700_000_000);
+        assert_eq!(ports[1]["run"]["scan_types"], serde_json::json!(["udp"]));
+        assert_eq!(ports[1]["host"]["ip"], "10.0.0.1");
+        assert_eq!(ports[1]["host"]["host_names"][0]["name"], "ns.lan");
+    }
+}
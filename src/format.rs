@@ -0,0 +1,554 @@
+use error_stack::{Report, ResultExt};
+use serde::Serialize;
+use std::{fmt, io::Write};
+use strum_macros::EnumString;
+
+#[cfg(feature = "csv")]
+use crate::table::TableWriter;
+use crate::{
+    flatten::{Flattener, HostDocument, PortDocument},
+    host::Host,
+    stream::Header,
+    table::TableOptions,
+    Error, NmapRun, Result,
+};
+
+/// Output formats of [`ReportWriter`]s.
+///
+/// YAML, TOML, Msgpack and CBOR need the cargo feature of the same name (in
+/// lowercase), CSV and TSV the `csv` feature. Without it, writing fails.
+#[derive(EnumString, Clone, Copy, Debug, PartialEq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Format {
+    /// A JSON document, an array when several reports are written
+    Json,
+    /// Indented [`Format::Json`]
+    Pretty,
+    /// A JSON report per line
+    Ndjson,
+    /// A JSON host per line, see [`HostDocument`]
+    NdjsonHost,
+    /// A JSON open port per line with its host, see [`PortDocument`]
+    NdjsonPort,
+    /// [`Format::NdjsonHost`] with the run context, see [`Flattener`]
+    FlatHost,
+    /// [`Format::NdjsonPort`] with the run context, see [`Flattener`]
+    FlatPort,
+    /// YAML documents separated by `---`
+    Yaml,
+    /// A TOML document, holds a single report
+    Toml,
+    /// Msgpack maps, one after another
+    Msgpack,
+    /// CBOR maps, one after another
+    Cbor,
+    /// A row per open port, see [`TableWriter`]
+    Csv,
+    /// [`Format::Csv`] with tabs
+    Tsv,
+    /// Nmap XML, see [`NmapRun::to_xml`]
+    Xml,
+}
+
+impl Format {
+    pub const ALL: [Format; 14] = [
+        Format::Json,
+        Format::Pretty,
+        Format::Ndjson,
+        Format::NdjsonHost,
+        Format::NdjsonPort,
+        Format::FlatHost,
+        Format::FlatPort,
+        Format::Yaml,
+        Format::Toml,
+        Format::Msgpack,
+        Format::Cbor,
+        Format::Csv,
+        Format::Tsv,
+        Format::Xml,
+    ];
+
+    /// A writer of the format to `output`, CSV and TSV with the columns of `options`
+    /// and the delimiter of the format.
+    #[must_use]
+    #[cfg_attr(
+        not(feature = "csv"),
+        allow(unused_variables, clippy::needless_pass_by_value)
+    )]
+    pub fn writer<'a>(
+        self,
+        output: impl Write + 'a,
+        options: TableOptions,
+    ) -> Box<dyn ReportWriter + 'a> {
+        match self {
+            Format::Json | Format::Pretty => Box::new(JsonWriter {
+                output,
+                pretty: self == Format::Pretty,
+                first: None,
+                count: 0,
+            }),
+            Format::Ndjson
+            | Format::NdjsonHost
+            | Format::NdjsonPort
+            | Format::FlatHost
+            | Format::FlatPort => Box::new(NdjsonWriter {
+                output,
+                format: self,
+                flattener: None,
+            }),
+            #[cfg(feature = "csv")]
+            Format::Csv | Format::Tsv => Box::new(TableWriter::new(
+                output,
+                TableOptions {
+                    delimiter: if self == Format::Tsv { b'\t' } else { b',' },
+                    ..options
+                },
+            )),
+            _ => Box::new(DocumentWriter {
+                output,
+                format: self,
+                count: 0,
+            }),
+        }
+    }
+
+    /// Whether hosts can be written one by one, e.g. while a report is streamed.
+    #[must_use]
+    pub fn writes_hosts(self) -> bool {
+        matches!(
+            self,
+            Format::NdjsonHost
+                | Format::NdjsonPort
+                | Format::FlatHost
+                | Format::FlatPort
+                | Format::Csv
+                | Format::Tsv
+        )
+    }
+
+    /// Whether an output holds a single report, so several inputs need an output directory.
+    #[must_use]
+    pub fn holds_one_report(self) -> bool {
+        matches!(self, Format::Toml | Format::Xml)
+    }
+
+    /// Extension of files of the format.
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json
+            | Format::Pretty
+            | Format::Ndjson
+            | Format::NdjsonHost
+            | Format::NdjsonPort
+            | Format::FlatHost
+            | Format::FlatPort => "json",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            Format::Msgpack => "msgpack",
+            Format::Cbor => "cbor",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+            Format::Xml => "xml",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Format::Json => "json",
+            Format::Pretty => "pretty",
+            Format::Ndjson => "ndjson",
+            Format::NdjsonHost => "ndjson-host",
+            Format::NdjsonPort => "ndjson-port",
+            Format::FlatHost => "flat-host",
+            Format::FlatPort => "flat-port",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            Format::Msgpack => "msgpack",
+            Format::Cbor => "cbor",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+            Format::Xml => "xml",
+        })
+    }
+}
+
+/// Reads the reports of [`Format::Json`], [`Format::Pretty`] and [`Format::Ndjson`]
+/// output: a single report, an array of reports or a report per line.
+pub fn read_json(json: &str) -> Result<Vec<NmapRun>> {
+    if json.trim_start().starts_with('[') {
+        return serde_json::from_str(json).change_context(Error::FailedToReadReport);
+    }
+
+    serde_json::Deserializer::from_str(json)
+        .into_iter::<NmapRun>()
+        .map(|report| report.change_context(Error::FailedToReadReport))
+        .collect()
+}
+
+/// Writes reports, or the hosts of a streamed report, in an output format.
+///
+/// A writer is fed every report of an output and finished once, so formats
+/// can write a header or close an array.
+pub trait ReportWriter {
+    fn write_report(&mut self, report: &NmapRun) -> Result<()>;
+
+    /// Called before the hosts of a streamed report.
+    fn write_header(&mut self, _header: &Header) -> Result<()> {
+        Ok(())
+    }
+
+    /// Writes a host of a streamed report, only formats with
+    /// [`Format::writes_hosts`] support it.
+    fn write_host(&mut self, _host: &Host) -> Result<()> {
+        whole_reports_only()
+    }
+
+    fn flush(&mut self) -> Result<()>;
+
+    /// Writes what the format needs after the last report and flushes.
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+struct JsonWriter<W: Write> {
+    output: W,
+    pretty: bool,
+    /// The first report, written as is if no other report follows
+    first: Option<Vec<u8>>,
+    count: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    fn serialize(&self, report: &NmapRun) -> Result<Vec<u8>> {
+        if self.pretty {
+            serde_json::to_vec_pretty(report)
+        } else {
+            serde_json::to_vec(report)
+        }
+        .change_context(Error::FailedToWriteReport)
+    }
+}
+
+impl<W: Write> ReportWriter for JsonWriter<W> {
+    fn write_report(&mut self, report: &NmapRun) -> Result<()> {
+        let json = self.serialize(report)?;
+        self.count += 1;
+
+        let result = match (self.count, self.first.take()) {
+            (1, _) => {
+                self.first = Some(json);
+                return Ok(());
+            }
+            (_, Some(first)) => self
+                .output
+                .write_all(b"[\n")
+                .and_then(|()| self.output.write_all(&first)),
+            (_, None) => Ok(()),
+        };
+
+        result
+            .and_then(|()| self.output.write_all(b",\n"))
+            .and_then(|()| self.output.write_all(&json))
+            .change_context(Error::FailedToWriteReport)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.output
+            .flush()
+            .change_context(Error::FailedToWriteReport)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let end: &[u8] = if self.count > 1 { b"\n]\n" } else { b"\n" };
+        if let Some(first) = self.first.take() {
+            self.output
+                .write_all(&first)
+                .change_context(Error::FailedToWriteReport)?;
+        }
+        if self.count > 0 {
+            self.output
+                .write_all(end)
+                .change_context(Error::FailedToWriteReport)?;
+        }
+        self.flush()
+    }
+}
+
+struct NdjsonWriter<W: Write> {
+    output: W,
+    format: Format,
+    /// Run context of the current report for flat formats
+    flattener: Option<Flattener>,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    fn line(&mut self, value: &impl Serialize) -> Result<()> {
+        serde_json::to_writer(&mut self.output, value)
+            .change_context(Error::FailedToWriteReport)?;
+        self.output
+            .write_all(b"\n")
+            .change_context(Error::FailedToWriteReport)
+    }
+}
+
+impl<W: Write> ReportWriter for NdjsonWriter<W> {
+    fn write_report(&mut self, report: &NmapRun) -> Result<()> {
+        if self.format == Format::Ndjson {
+            return self.line(report);
+        }
+        self.flattener = Some(Flattener::new(report));
+        for host in report.hosts.iter().flatten() {
+            self.write_host(host)?;
+        }
+        Ok(())
+    }
+
+    fn write_host(&mut self, host: &Host) -> Result<()> {
+        match self.format {
+            Format::NdjsonHost => self.line(&HostDocument::new(host)),
+            Format::NdjsonPort => PortDocument::of_host(host)
+                .iter()
+                .try_for_each(|document| self.line(document)),
+            Format::FlatHost | Format::FlatPort => {
+                let flattener = self
+                    .flattener
+                    .take()
+                    .ok_or(Error::FailedToWriteReport)
+                    .attach_printable("a flat format needs the run header before hosts")?;
+                let result = if self.format == Format::FlatHost {
+                    self.line(&flattener.host(host))
+                } else {
+                    flattener
+                        .ports(host)
+                        .iter()
+                        .try_for_each(|document| self.line(document))
+                };
+                self.flattener = Some(flattener);
+                result
+            }
+            _ => whole_reports_only(),
+        }
+    }
+
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        self.flattener = Some(Flattener::of_header(header));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.output
+            .flush()
+            .change_context(Error::FailedToWriteReport)
+    }
+}
+
+fn whole_reports_only() -> Result<()> {
+    Err(Report::new(Error::FailedToWriteReport))
+        .attach_printable("the format writes whole reports only")
+}
+
+/// Formats that write a document per report.
+struct DocumentWriter<W: Write> {
+    output: W,
+    format: Format,
+    count: usize,
+}
+
+impl<W: Write> ReportWriter for DocumentWriter<W> {
+    fn write_report(&mut self, report: &NmapRun) -> Result<()> {
+        let output = &mut self.output;
+        let result = match self.format {
+            #[cfg(feature = "yaml")]
+            Format::Yaml => {
+                if self.count > 0 {
+                    output
+                        .write_all(b"---\n")
+                        .change_context(Error::FailedToWriteReport)?;
+                }
+                serde_yaml_ng::to_writer(output, report).change_context(Error::FailedToWriteReport)
+            }
+            #[cfg(feature = "toml")]
+            Format::Toml if self.count > 0 => Err(Report::new(Error::FailedToWriteReport))
+                .attach_printable("a TOML document holds a single report"),
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::to_string(report)
+                .change_context(Error::FailedToWriteReport)
+                .and_then(|toml| {
+                    output
+                        .write_all(toml.as_bytes())
+                        .change_context(Error::FailedToWriteReport)
+                }),
+            #[cfg(feature = "msgpack")]
+            Format::Msgpack => rmp_serde::encode::write_named(output, report)
+                .change_context(Error::FailedToWriteReport),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => {
+                ciborium::into_writer(report, output).change_context(Error::FailedToWriteReport)
+            }
+            Format::Xml if self.count > 0 => Err(Report::new(Error::FailedToWriteReport))
+                .attach_printable("an XML document holds a single report"),
+            Format::Xml => output
+                .write_all(report.to_xml().as_bytes())
+                .change_context(Error::FailedToWriteReport),
+            _ => Err(Report::new(Error::FailedToWriteReport))
+                .attach_printable("n2j is built without the cargo feature of the format"),
+        };
+        self.count += 1;
+
+        result.attach_printable_lazy(|| format!("format: {}", self.format))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.output
+            .flush()
+            .change_context(Error::FailedToWriteReport)
+    }
+}
+
+#[cfg(feature = "csv")]
+impl<W: Write> ReportWriter for TableWriter<W> {
+    fn write_report(&mut self, report: &NmapRun) -> Result<()> {
+        for host in report.hosts.iter().flatten() {
+            TableWriter::write_host(self, host)?;
+        }
+        Ok(())
+    }
+
+    fn write_host(&mut self, host: &Host) -> Result<()> {
+        TableWriter::write_host(self, host)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        TableWriter::flush(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{format::Format, table::TableOptions, NmapRun};
+
+    const XML: &str = r#"<nmaprun scanner="nmap" args="nmap -sV 10.0.0.1" start="1" version="7.94" xmloutputversion="1.05">
+<scaninfo type="syn" protocol="tcp" numservices="2" services="22,80"/>
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="22"><state state="open"/><service name="ssh" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="80"><state state="closed"/></port>
+</ports>
+</host>
+<runstats><finished time="2" elapsed="1.00"/><hosts up="1" down="0" total="1"/></runstats>
+</nmaprun>"#;
+
+    fn write(format: Format, reports: &[&NmapRun]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut writer = format.writer(&mut out, TableOptions::default());
+        for report in reports {
+            writer.write_report(report).unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+        out
+    }
+
+    fn text(format: Format, reports: &[&NmapRun]) -> String {
+        String::from_utf8(write(format, reports)).unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        let report = NmapRun::parse(XML).unwrap();
+
+        let json = serde_json::from_str::<serde_json::Value>(&text(Format::Json, &[&report]));
+        assert_eq!(json.unwrap()["start"], 1);
+        let array =
+            serde_json::from_str::<Vec<NmapRun>>(&text(Format::Pretty, &[&report, &report]));
+        assert_eq!(array.unwrap().len(), 2);
+
+        assert_eq!(text(Format::Ndjson, &[&report, &report]).lines().count(), 2);
+        assert_eq!(text(Format::NdjsonHost, &[&report]).lines().count(), 1);
+        let ports = text(Format::NdjsonPort, &[&report]);
+        assert_eq!(ports.lines().count(), 1);
+        assert!(ports.starts_with(r#"{"host":{"ip":"10.0.0.1","#));
+
+        // The flat formats are the ndjson ones with the run context first
+        let flat = text(Format::FlatPort, &[&report]);
+        let (run, port) = flat.split_once(r#"},"host":"#).unwrap();
+        assert!(run.starts_with(r#"{"run":{"scanner":"nmap","#));
+        assert_eq!(format!(r#"{{"host":{port}"#), ports);
+        assert!(text(Format::FlatHost, &[&report]).ends_with(
+            text(Format::NdjsonHost, &[&report])
+                .strip_prefix('{')
+                .unwrap()
+        ));
+    }
+
+    #[test]
+    fn read_json() {
+        let report = NmapRun::parse(XML).unwrap();
+
+        for format in [Format::Json, Format::Pretty, Format::Ndjson] {
+            let one = super::read_json(&text(format, &[&report])).unwrap();
+            assert_eq!(one.len(), 1, "{format}");
+            let two = super::read_json(&text(format, &[&report, &report])).unwrap();
+            assert_eq!(two.len(), 2, "{format}");
+            assert_eq!(format!("{:?}", two[1]), format!("{report:?}"), "{format}");
+        }
+
+        assert!(super::read_json("[{}]").is_err());
+    }
+
+    #[test]
+    fn xml_holds_one_report() {
+        let report = NmapRun::parse(XML).unwrap();
+        assert!(Format::Xml.holds_one_report());
+
+        let xml = text(Format::Xml, &[&report]);
+        assert_eq!(NmapRun::parse(&xml).unwrap().version, "7.94");
+
+        let mut out = Vec::new();
+        let mut writer = Format::Xml.writer(&mut out, TableOptions::default());
+        writer.write_report(&report).unwrap();
+        assert!(writer.write_report(&report).is_err());
+    }
+
+    #[cfg(all(
+        feature = "yaml",
+        feature = "toml",
+        feature = "msgpack",
+        feature = "cbor"
+    ))]
+    #[test]
+    fn document_formats() {
+        let report = NmapRun::parse(XML).unwrap();
+
+        let yaml = text(Format::Yaml, &[&report, &report]);
+        assert_eq!(yaml.matches("scanner: nmap").count(), 2);
+        let toml = toml::from_str::<NmapRun>(&text(Format::Toml, &[&report])).unwrap();
+        assert_eq!(toml.hosts.unwrap()[0].ports.as_ref().unwrap().len(), 2);
+
+        let msgpack = rmp_serde::from_slice::<NmapRun>(&write(Format::Msgpack, &[&report]));
+        assert_eq!(msgpack.unwrap().version, "7.94");
+        let cbor = ciborium::from_reader::<NmapRun, _>(&write(Format::Cbor, &[&report])[..]);
+        assert_eq!(cbor.unwrap().version, "7.94");
+
+        let mut out = Vec::new();
+        let mut writer = Format::Toml.writer(&mut out, TableOptions::default());
+        writer.write_report(&report).unwrap();
+        assert!(writer.write_report(&report).is_err());
+        assert!(writer
+            .write_host(&report.hosts.clone().unwrap()[0])
+            .is_err());
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn disabled_format() {
+        let report = NmapRun::parse(XML).unwrap();
+        let mut out = Vec::new();
+        let mut writer = Format::Yaml.writer(&mut out, TableOptions::default());
+        assert!(writer.write_report(&report).is_err());
+    }
+}
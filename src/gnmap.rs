@@ -0,0 +1,338 @@
+use error_stack::{Report, ResultExt};
+use std::net::IpAddr;
+
+use crate::{
+    address::Address,
+    host::Host,
+    hostname::{Hostname, Type},
+    port::{ExtraPorts, Port, PortProtocol, PortState, PortStatus, Service, ServiceTunnel},
+    runstats::{Finished, Hosts, RunStats},
+    status::{HostState, Status},
+    Error, NmapRun, Result,
+};
+
+impl NmapRun {
+    /// Parses nmap grepable output (`-oG`).
+    ///
+    /// The format keeps much less than XML: host status, addresses, PTR names,
+    /// ports and the ignored state count. The version column of a port joins
+    /// product, version and extra info, they are split on a best effort basis.
+    /// Times in the comments are the local time of the scanning machine
+    /// without a zone, they are read as UTC.
+    pub fn parse_gnmap(text: &str) -> Result<Self> {
+        let mut nmaprun = NmapRun::new("nmap");
+        let mut hosts: Vec<Host> = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                parse_comment(comment, &mut nmaprun);
+            } else if let Some(line) = line.strip_prefix("Host: ") {
+                parse_host_line(line, &mut hosts)
+                    .attach_printable_lazy(|| format!("line: {}", index + 1))?;
+            }
+        }
+
+        nmaprun.hosts = Some(hosts).filter(|v| !v.is_empty());
+
+        Ok(nmaprun)
+    }
+}
+
+/// Reads the `# Nmap ... scan initiated` and `# Nmap done at` comments,
+/// shared by grepable and normal output.
+pub(crate) fn parse_comment(comment: &str, nmaprun: &mut NmapRun) {
+    if let Some(done) = comment.strip_prefix("Nmap done at ") {
+        nmaprun.runstats = Some(parse_done(done));
+    } else if let Some((version, started)) = comment
+        .strip_prefix("Nmap ")
+        .and_then(|rest| rest.split_once(" scan initiated "))
+    {
+        let (date, args) = started.split_once(" as: ").unwrap_or((started, ""));
+        nmaprun.version = version.to_string();
+        nmaprun.args = args.to_string();
+        nmaprun.start = parse_ctime(date)
+            .and_then(|time| u32::try_from(time).ok())
+            .unwrap_or(0);
+    }
+}
+
+/// `Sat Jun 17 20:57:15 2023 -- 1 IP address (1 host up) scanned in 14.12 seconds`
+fn parse_done(done: &str) -> RunStats {
+    let (date, summary) = done.split_once(" -- ").unwrap_or((done, ""));
+    let (elapsed, hosts) = parse_summary(summary);
+
+    RunStats {
+        finished: Some(Finished {
+            time: parse_ctime(date).unwrap_or(0),
+            timestr: Some(date.to_string()),
+            elapsed,
+            summary: Some(format!("Nmap done at {date}; {summary}")),
+            exit: None,
+            errormsg: None,
+        }),
+        hosts,
+    }
+}
+
+/// `1 IP address (1 host up) scanned in 14.12 seconds`
+pub(crate) fn parse_summary(summary: &str) -> (Option<f64>, Option<Hosts>) {
+    let elapsed = summary
+        .rsplit_once("scanned in ")
+        .and_then(|(_, elapsed)| elapsed.trim_end_matches(" seconds").parse::<f64>().ok());
+
+    let total = summary
+        .split_once(" IP address")
+        .and_then(|(total, _)| total.parse::<i64>().ok());
+    let up = summary
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(' '))
+        .and_then(|(up, _)| up.parse::<i64>().ok());
+
+    let hosts = total.zip(up).map(|(total, up)| Hosts {
+        up,
+        down: total - up,
+        total,
+    });
+
+    (elapsed, hosts)
+}
+
+/// Parses `10.0.0.1 (name)<TAB>Status: Up` or `10.0.0.1 (name)<TAB>Ports: ...`,
+/// the lines of one host are merged.
+fn parse_host_line(line: &str, hosts: &mut Vec<Host>) -> Result<()> {
+    let mut fields = line.split('\t');
+    let target = fields.next().unwrap_or_default();
+
+    let (addr, name) = target.split_once(' ').unwrap_or((target, ""));
+    let addr = addr
+        .parse::<IpAddr>()
+        .change_context(Error::FailedToParseGnmap)
+        .attach_printable_lazy(|| format!("address: \"{addr}\""))?;
+    let address = Address::IpAddr(addr);
+
+    if hosts
+        .last()
+        .is_none_or(|host| host.addresses.first() != Some(&address))
+    {
+        let status = Status {
+            state: HostState::Up,
+            reason: None,
+            reason_ttl: None,
+        };
+        let mut host = Host::new(status, vec![address]);
+
+        let name = name.trim_start_matches('(').trim_end_matches(')');
+        if !name.is_empty() {
+            host.host_names = Some(vec![Hostname {
+                name: name.to_string(),
+                ttype: Type::Ptr,
+            }]);
+        }
+
+        hosts.push(host);
+    }
+
+    let host = hosts.last_mut().expect("host is pushed above");
+
+    for field in fields {
+        let (key, value) = field.split_once(": ").unwrap_or((field, ""));
+
+        match key {
+            "Status" => {
+                host.status.state = value
+                    .to_lowercase()
+                    .parse::<HostState>()
+                    .change_context(Error::FailedToParseGnmap)
+                    .attach_printable_lazy(|| format!("status: \"{value}\""))?;
+            }
+            "Ports" => host.ports = Some(parse_ports(value)?).filter(|v| !v.is_empty()),
+            "Ignored State" => {
+                host.extraports = Some(vec![parse_ignored(value)?]);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `22/open/tcp//ssh//OpenSSH 8.2p1/, 443/open/tcp//ssl|https///`
+///
+/// Every port has seven `/`-terminated columns: port, state, protocol, owner,
+/// service, RPC info and version.
+fn parse_ports(value: &str) -> Result<Vec<Port>> {
+    let columns = value.split('/').collect::<Vec<_>>();
+    let ports = columns.chunks_exact(7);
+
+    if ports.remainder() != [""] {
+        return Err(Report::new(Error::FailedToParseGnmap))
+            .attach_printable(format!("ports: \"{value}\""));
+    }
+
+    ports
+        .map(|port| {
+            let [number, state, protocol, _, service, _, version] = port else {
+                unreachable!("chunks have seven columns");
+            };
+            parse_port(number, state, protocol, service, version)
+                .attach_printable_lazy(|| format!("port: \"{}\"", port.join("/")))
+        })
+        .collect()
+}
+
+fn parse_port(
+    number: &str,
+    state: &str,
+    protocol: &str,
+    service: &str,
+    version: &str,
+) -> Result<Port> {
+    let port_number = number
+        .trim_start_matches([',', ' '])
+        .parse::<u16>()
+        .change_context(Error::FailedToParseGnmap)?;
+
+    let state = state
+        .parse::<PortState>()
+        .change_context(Error::FailedToParseGnmap)?;
+
+    let protocol = protocol
+        .parse::<PortProtocol>()
+        .change_context(Error::FailedToParseGnmap)?;
+
+    let service = (!service.is_empty() || !version.is_empty()).then(|| {
+        let (tunnel, name) = match service.strip_prefix("ssl|") {
+            Some(name) => (Some(ServiceTunnel::Ssl), name),
+            None => (None, service),
+        };
+
+        let mut service = Service::new(name.to_string());
+        service.tunnel = tunnel;
+        service.set_version_column(version);
+        service
+    });
+
+    Ok(Port {
+        protocol,
+        port_number,
+        status: PortStatus {
+            state,
+            reason: None,
+            reason_ttl: None,
+        },
+        service,
+        scripts: None,
+        sources: None,
+    })
+}
+
+/// `closed (998)`
+fn parse_ignored(value: &str) -> Result<ExtraPorts> {
+    let (state, count) = value
+        .split_once(" (")
+        .ok_or(Error::FailedToParseGnmap)
+        .attach_printable_lazy(|| format!("ignored state: \"{value}\""))?;
+
+    let state = state
+        .parse::<PortState>()
+        .change_context(Error::FailedToParseGnmap)
+        .attach_printable_lazy(|| format!("ignored state: \"{value}\""))?;
+
+    let count = count
+        .trim_end_matches(')')
+        .parse::<u32>()
+        .change_context(Error::FailedToParseGnmap)
+        .attach_printable_lazy(|| format!("ignored state: \"{value}\""))?;
+
+    Ok(ExtraPorts {
+        state,
+        count,
+        extrareasons: None,
+    })
+}
+
+/// Parses a `ctime` date like `Sat Jun 17 20:57:15 2023` as UTC.
+pub(crate) fn parse_ctime(date: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let mut parts = date.split_whitespace().skip(1);
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? + 1;
+    let day = parts.next()?.parse::<i64>().ok()?;
+
+    let mut time = parts.next()?.split(':').map(str::parse::<i64>);
+    let hours = time.next()?.ok()?;
+    let minutes = time.next()?.ok()?;
+    let seconds = time.next()?.ok()?;
+
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = i64::try_from(month).ok()?;
+
+    Some(days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        port::{PortState, ServiceTunnel},
+        status::HostState,
+        NmapRun,
+    };
+
+    const GNMAP: &str = "\
+# Nmap 7.94 scan initiated Sat Jun 17 20:57:01 2023 as: nmap -sV -oG scan.gnmap 10.0.0.0/30
+Host: 10.0.0.1 (router.lan)\tStatus: Up
+Host: 10.0.0.1 (router.lan)\tPorts: 22/open/tcp//ssh//OpenSSH 8.4 (protocol 2.0)/, 443/open/tcp//ssl|https///, 53/open|filtered/udp//domain///\tIgnored State: closed (997)
+Host: 10.0.0.2 ()\tStatus: Down
+# Nmap done at Sat Jun 17 20:57:15 2023 -- 4 IP addresses (1 host up) scanned in 14.12 seconds
+";
+
+    #[test]
+    fn parse_gnmap() {
+        let report = NmapRun::parse_gnmap(GNMAP).unwrap();
+
+        assert_eq!(report.version, "7.94");
+        assert_eq!(report.args, "nmap -sV -oG scan.gnmap 10.0.0.0/30");
+        assert_eq!(report.start, 1_687_035_421);
+
+        let hosts = report.hosts.as_ref().unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[1].status.state, HostState::Down);
+
+        let ports = hosts[0].ports.as_ref().unwrap();
+        let ssh = ports[0].service.as_ref().unwrap();
+        assert_eq!(ssh.product.as_deref(), Some("OpenSSH"));
+        assert_eq!(ssh.version.as_deref(), Some("8.4"));
+        assert_eq!(ssh.extra_info.as_deref(), Some("protocol 2.0"));
+        assert_eq!(ssh.confidence_level, None);
+        let https = ports[1].service.as_ref().unwrap();
+        assert_eq!(
+            (https.name.as_str(), &https.tunnel),
+            ("https", &Some(ServiceTunnel::Ssl))
+        );
+        assert_eq!(ports[2].status.state, PortState::OpenFiltered);
+
+        let extraports = hosts[0].extraports.as_ref().unwrap();
+        assert_eq!(
+            (&extraports[0].state, extraports[0].count),
+            (&PortState::Closed, 997)
+        );
+
+        let runstats = report.runstats.unwrap();
+        assert_eq!(runstats.hosts.unwrap().down, 3);
+        assert_eq!(runstats.finished.unwrap().elapsed, Some(14.12));
+    }
+}
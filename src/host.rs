@@ -2,15 +2,21 @@ use error_stack::ResultExt;
 use roxmltree::Node;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
+use std::net::IpAddr;
 
 use crate::{
     address::Address,
+    context::Context,
     distance::Distance,
     hostname::Hostname,
     os::Os,
     port::{ExtraPorts, Port, Ports},
-    script::Script,
+    script::{parse_scripts_node, Script},
+    sequence::{IpIdSequence, TcpSequence, TcpTsSequence},
     status::Status,
+    times::Times,
+    trace::Trace,
+    uptime::Uptime,
     Attribute, Element, Error, Result,
 };
 
@@ -29,11 +35,19 @@ pub struct Host {
     pub extraports: Option<Vec<ExtraPorts>>,
     pub os: Option<Os>,
     pub distance: Option<Distance>,
+    pub uptime: Option<Uptime>,
+    pub tcp_sequence: Option<TcpSequence>,
+    pub ip_id_sequence: Option<IpIdSequence>,
+    pub tcp_ts_sequence: Option<TcpTsSequence>,
+    pub times: Option<Times>,
     pub host_scripts: Option<Vec<Script>>,
+    pub trace: Option<Trace>,
+    /// Indexes in `NmapRun::sources` of the merged reports that listed the host.
+    pub sources: Option<Vec<usize>>,
 }
 
 impl Host {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let start_time = node
             .attribute("starttime")
             .map(str::parse::<u32>)
@@ -62,21 +76,40 @@ impl Host {
         let mut extraports = None;
         let mut os = None;
         let mut distance = None;
+        let mut uptime = None;
+        let mut tcp_sequence = None;
+        let mut ip_id_sequence = None;
+        let mut tcp_ts_sequence = None;
+        let mut times = None;
         let mut host_scripts = None;
+        let mut trace = None;
 
         for child in node.children() {
             match child.tag_name().name() {
-                "status" => status = Some(Status::parse(child)?),
-                "address" => addresses.push(Address::parse(child)?),
-                "hostnames" => host_names = parse_host_names_node(child)?,
+                "status" => status = ctx.child(child, Status::parse)?,
+                "address" => addresses.extend(ctx.leaf(child, Address::parse)?),
+                "hostnames" => host_names = parse_host_names_node(child, ctx)?,
                 "ports" => {
-                    let object = Ports::parse(child)?;
-                    ports = object.ports;
-                    extraports = object.extraports;
+                    if let Some(object) = ctx.child(child, Ports::parse)? {
+                        ports = object.ports;
+                        extraports = object.extraports;
+                    }
                 }
-                "os" => os = Some(Os::parse(child)?),
-                "distance" => distance = Some(Distance::parse(child)?),
-                "hostscript" => host_scripts = parse_host_scripts_node(child)?,
+                "os" => os = ctx.child(child, Os::parse)?,
+                "distance" => distance = ctx.leaf(child, Distance::parse)?,
+                "uptime" if !is_placeholder(child) => uptime = ctx.leaf(child, Uptime::parse)?,
+                "tcpsequence" if !is_placeholder(child) => {
+                    tcp_sequence = ctx.leaf(child, TcpSequence::parse)?;
+                }
+                "ipidsequence" if !is_placeholder(child) => {
+                    ip_id_sequence = ctx.leaf(child, IpIdSequence::parse)?;
+                }
+                "tcptssequence" if !is_placeholder(child) => {
+                    tcp_ts_sequence = ctx.leaf(child, TcpTsSequence::parse)?;
+                }
+                "times" if !is_placeholder(child) => times = ctx.leaf(child, Times::parse)?,
+                "hostscript" => host_scripts = parse_scripts_node(child, ctx)?,
+                "trace" => trace = ctx.child(child, Trace::parse)?,
                 _ => {}
             }
         }
@@ -98,29 +131,63 @@ impl Host {
             extraports,
             os,
             distance,
+            uptime,
+            tcp_sequence,
+            ip_id_sequence,
+            tcp_ts_sequence,
+            times,
             host_scripts,
+            trace,
+            sources: None,
         })
     }
-}
-
-fn parse_host_scripts_node(node: Node) -> Result<Option<Vec<Script>>> {
-    let mut scripts = Vec::new();
 
-    for child in node.children() {
-        if child.tag_name().name() == "script" {
-            scripts.push(Script::parse(child)?);
+    /// Host with only the required data, used by the parsers of other formats.
+    pub(crate) fn new(status: Status, addresses: Vec<Address>) -> Self {
+        Host {
+            start_time: None,
+            end_time: None,
+            timed_out: None,
+            status,
+            addresses,
+            host_names: None,
+            ports: None,
+            extraports: None,
+            os: None,
+            distance: None,
+            uptime: None,
+            tcp_sequence: None,
+            ip_id_sequence: None,
+            tcp_ts_sequence: None,
+            times: None,
+            host_scripts: None,
+            trace: None,
+            sources: None,
         }
     }
 
-    Ok(Some(scripts).filter(|v| !v.is_empty()))
+    /// First IP address of the host, hosts are identified by it across reports.
+    #[must_use]
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.addresses.iter().find_map(|address| match address {
+            Address::IpAddr(ip) => Some(*ip),
+            Address::MacAddr { .. } => None,
+        })
+    }
+}
+
+/// Some tools that rewrite nmap reports emit elements with every attribute
+/// left empty (e.g. `<uptime seconds="" lastboot=""/>`); these carry no data.
+fn is_placeholder(node: Node) -> bool {
+    node.attributes().all(|attr| attr.value().is_empty())
 }
 
-fn parse_host_names_node(node: Node) -> Result<Option<Vec<Hostname>>> {
+fn parse_host_names_node(node: Node, ctx: &mut Context) -> Result<Option<Vec<Hostname>>> {
     let mut hostnames = Vec::new();
 
     for child in node.children() {
         if child.tag_name().name() == "hostname" {
-            hostnames.push(Hostname::parse(child)?);
+            hostnames.extend(ctx.child(child, Hostname::parse)?);
         }
     }
 
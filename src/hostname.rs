@@ -1,7 +1,11 @@
-use crate::{Attribute, Error, Result};
+use crate::{
+    context::{Context, OtherVariant},
+    Attribute, Error, Result,
+};
 use error_stack::ResultExt;
 use roxmltree::Node;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use strum_macros::EnumString;
 
 #[derive(EnumString, Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -12,6 +16,26 @@ pub enum Type {
     Dns,
     #[strum(serialize = "PTR")]
     Ptr,
+    #[strum(disabled)]
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl OtherVariant for Type {
+    fn other(value: String) -> Self {
+        Type::Other(value)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Type::User => "user",
+            Type::Dns => "dns",
+            Type::Ptr => "PTR",
+            Type::Other(value) => value,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -22,7 +46,7 @@ pub struct Hostname {
 }
 
 impl Hostname {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let name = node
             .attribute("name")
             .ok_or(Error::MissedAttribute)
@@ -33,8 +57,7 @@ impl Hostname {
             .attribute("type")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("type", "hostname")))
-            .map(str::parse::<Type>)?
-            .change_context(Error::FailedToParseAttribute)
+            .and_then(|value| ctx.variant::<Type>(node, "type", value))
             .attach_printable(Attribute(("type", "hostname")))?;
 
         Ok(Hostname { name, ttype })
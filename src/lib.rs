@@ -4,15 +4,39 @@
 #![allow(clippy::missing_errors_doc)]
 
 pub mod address;
+pub mod context;
+pub mod diff;
 pub mod distance;
+pub mod event;
+#[cfg(feature = "filter")]
+pub mod filter;
+#[cfg(test)]
+mod fixture;
+pub mod flatten;
+pub mod format;
+pub mod gnmap;
 pub mod host;
 pub mod hostname;
+pub mod masscan;
+pub mod merge;
+pub mod normal;
 pub mod os;
 pub mod port;
+pub mod recover;
 pub mod runstats;
+pub mod rustscan;
 pub mod scaninfo;
 pub mod script;
+pub mod sequence;
 pub mod status;
+pub mod stream;
+pub mod table;
+pub mod tail;
+pub mod times;
+pub mod trace;
+pub mod uptime;
+pub mod warning;
+pub mod xml;
 
 use error_stack::ResultExt;
 use roxmltree::Document;
@@ -20,9 +44,15 @@ use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use std::{borrow::Cow, fmt};
 
+use crate::context::{Context, ParseOptions};
+use crate::event::Event;
 use crate::host::Host;
-use crate::runstats::RunStats;
+use crate::merge::SourceReport;
+use crate::runstats::{Exit, Finished, RunStats};
 use crate::scaninfo::ScanInfo;
+use crate::script::{parse_scripts_node, Script};
+use crate::stream::Header;
+use crate::warning::Warning;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -38,8 +68,30 @@ pub enum Error {
     FailedToParseAttribute,
     #[error("failed to parse XML document")]
     FailedToParseXml,
-    #[error("this tool works only with \"nmap\" report")]
+    #[error("unsupported scanner, only nmap and masscan reports are read")]
     InvalidScannerType,
+    #[error("unsupported address type")]
+    UnsupportedAddressType,
+    #[error("failed to parse grepable output")]
+    FailedToParseGnmap,
+    #[error("failed to parse normal output")]
+    FailedToParseNormal,
+    #[error("failed to parse masscan output")]
+    FailedToParseMasscan,
+    #[error("failed to parse rustscan output")]
+    FailedToParseRustscan,
+    #[error(
+        "masscan reports can't be streamed, records of a host are merged over the whole report"
+    )]
+    UnsupportedStream,
+    #[error("failed to parse filter expression")]
+    FailedToParseFilter,
+    #[error("failed to write table")]
+    FailedToWriteTable,
+    #[error("failed to write report")]
+    FailedToWriteReport,
+    #[error("failed to read n2j JSON report")]
+    FailedToReadReport,
 }
 
 type Result<T> = error_stack::Result<T, Error>;
@@ -72,18 +124,57 @@ pub struct NmapRun {
     pub version: String,
     pub xmloutputversion: String,
     pub scaninfos: Option<Vec<ScanInfo>>,
+    pub prescripts: Option<Vec<Script>>,
+    pub postscripts: Option<Vec<Script>>,
+    pub events: Option<Vec<Event>>,
     pub runstats: Option<RunStats>,
     pub hosts: Option<Vec<Host>>,
+    /// Reports a merged run was made of, see [`NmapRun::merge`].
+    pub sources: Option<Vec<SourceReport>>,
 }
 
 impl NmapRun {
+    /// Run without any data, used by the parsers of other formats.
+    pub(crate) fn new(scanner: &str) -> Self {
+        NmapRun {
+            scanner: scanner.to_string(),
+            args: String::new(),
+            start: 0,
+            version: String::new(),
+            xmloutputversion: String::new(),
+            scaninfos: None,
+            prescripts: None,
+            postscripts: None,
+            events: None,
+            runstats: None,
+            hosts: None,
+            sources: None,
+        }
+    }
+
+    /// Summary of the `<runstats><finished>` element, absent for interrupted scans.
+    #[must_use]
+    pub fn finished(&self) -> Option<&Finished> {
+        self.runstats.as_ref()?.finished.as_ref()
+    }
+
+    /// Exit status of the scan as reported by nmap.
+    #[must_use]
+    pub fn exit(&self) -> Option<&Exit> {
+        self.finished()?.exit.as_ref()
+    }
+
+    /// Error message of a scan that ended with `exit="error"`.
+    #[must_use]
+    pub fn errormsg(&self) -> Option<&str> {
+        self.finished()?.errormsg.as_deref()
+    }
+
     pub fn parse_and_fix<'a>(xml: impl Into<Cow<'a, str>>) -> Result<Self> {
         let xml = xml.into();
         let mut nmaprun = NmapRun::parse(xml.as_ref());
         if let Err(ref e) = nmaprun {
-            if e.downcast_ref::<roxmltree::Error>()
-                .is_some_and(|e| matches!(e, roxmltree::Error::UnclosedRootNode))
-            {
+            if is_unclosed_root(e) {
                 nmaprun = NmapRun::parse(&(xml + "\n</nmaprun>"));
             }
         }
@@ -91,69 +182,86 @@ impl NmapRun {
     }
 
     pub fn parse(xml: &str) -> Result<Self> {
+        NmapRun::parse_with_context(xml, &mut Context::default())
+    }
+
+    /// Parses a report with the given options and returns the collected warnings.
+    ///
+    /// In lenient mode (`strict: false`) a missing `</nmaprun>` is fixed as
+    /// in [`NmapRun::parse_and_fix`] and reported as a warning.
+    pub fn parse_with_options(xml: &str, options: ParseOptions) -> Result<(Self, Vec<Warning>)> {
+        let mut warnings = Vec::new();
+        let mut ctx = Context::new(options);
+        let mut nmaprun = NmapRun::parse_with_context(xml, &mut ctx);
+
+        if let Err(ref e) = nmaprun {
+            if !options.strict && is_unclosed_root(e) {
+                warnings.push(Warning {
+                    message: "missing closing tag, the report is incomplete".to_string(),
+                    element: Some("nmaprun".to_string()),
+                    offset: u64::try_from(xml.len()).ok(),
+                    address: None,
+                    location: None,
+                });
+                ctx = Context::new(options);
+                nmaprun = NmapRun::parse_with_context(&format!("{xml}\n</nmaprun>"), &mut ctx);
+            }
+        }
+
+        let nmaprun = nmaprun?;
+        warnings.extend(ctx.into_warnings());
+
+        Ok((nmaprun, warnings))
+    }
+
+    fn parse_with_context(xml: &str, ctx: &mut Context) -> Result<Self> {
         let opt = roxmltree::ParsingOptions {
             allow_dtd: true,
             ..Default::default()
         };
 
-        let doc = Document::parse_with_options(xml, opt).change_context(Error::FailedToParseXml)?;
+        let doc = Document::parse_with_options(xml, opt).map_err(|e| ctx.syntax_error(e))?;
 
         let root_element = doc.root_element();
-        if root_element.tag_name().name() != "nmaprun" {
-            return Err(Error::MissedElement).attach_printable(Element(("nmaprun", "root")));
-        }
 
-        let start = root_element
-            .attribute("start")
-            .ok_or(Error::MissedAttribute)
-            .attach_printable(Attribute(("start", "nmaprun")))
-            .map(str::parse::<u32>)?
-            .change_context(Error::FailedToParseAttribute)
-            .attach_printable(Attribute(("start", "nmaprun")))?;
-
-        let scanner = root_element
-            .attribute("scanner")
-            .ok_or(Error::MissedAttribute)
-            .attach_printable(Attribute(("scanner", "nmaprun")))?
-            .to_string();
-
-        // Check that we're working with NMAP only
-        if scanner != "nmap" {
-            return Err(error_stack::Report::new(Error::InvalidScannerType));
-        }
+        let Header {
+            scanner,
+            args,
+            start,
+            version,
+            xmloutputversion,
+            ..
+        } = Header::parse(root_element).map_err(|e| ctx.located(e, root_element))?;
 
-        let args = root_element
-            .attribute("args")
-            .ok_or(Error::MissedAttribute)
-            .attach_printable(Attribute(("args", "nmaprun")))?
-            .to_string();
-
-        let version = root_element
-            .attribute("version")
-            .ok_or(Error::MissedAttribute)
-            .attach_printable(Attribute(("version", "nmaprun")))?
-            .to_string();
-
-        let xmloutputversion = root_element
-            .attribute("xmloutputversion")
-            .ok_or(Error::MissedAttribute)
-            .attach_printable(Attribute(("xmloutputversion", "nmaprun")))?
-            .to_string();
+        let masscan = scanner == "masscan";
 
         let parts = || -> Result<Self> {
             let mut scaninfos = Vec::new();
             let mut hosts = Vec::new();
+            let mut events = Vec::new();
+            let mut prescripts = None;
+            let mut postscripts = None;
             let mut runstats = None;
 
             for child in root_element.children() {
                 match child.tag_name().name() {
-                    "scaninfo" => scaninfos.push(ScanInfo::parse(child)?),
-                    "host" => hosts.push(Host::parse(child)?),
-                    "runstats" => runstats = Some(RunStats::parse(child)?),
-                    _ => {}
+                    "scaninfo" if masscan => {
+                        scaninfos.extend(ctx.child(child, masscan::parse_scaninfo)?);
+                    }
+                    "scaninfo" => scaninfos.extend(ctx.child(child, ScanInfo::parse)?),
+                    "host" if masscan => hosts.extend(ctx.child(child, masscan::parse_host)?),
+                    "host" => hosts.extend(ctx.child(child, Host::parse)?),
+                    "prescript" => prescripts = parse_scripts_node(child, ctx)?,
+                    "postscript" => postscripts = parse_scripts_node(child, ctx)?,
+                    "runstats" => runstats = ctx.child(child, RunStats::parse)?,
+                    _ => events.extend(ctx.leaf(child, Event::parse)?.flatten()),
                 }
             }
 
+            if masscan {
+                hosts = masscan::merge_records(hosts);
+            }
+
             Ok(NmapRun {
                 scaninfos: Some(scaninfos).filter(|v| !v.is_empty()),
                 hosts: Some(hosts).filter(|v| !v.is_empty()),
@@ -162,7 +270,11 @@ impl NmapRun {
                 xmloutputversion: xmloutputversion.clone(),
                 args,
                 start,
+                prescripts,
+                postscripts,
+                events: Some(events).filter(|v| !v.is_empty()),
                 runstats,
+                sources: None,
             })
         };
 
@@ -173,6 +285,12 @@ impl NmapRun {
     }
 }
 
+fn is_unclosed_root(report: &error_stack::Report<Error>) -> bool {
+    report
+        .downcast_ref::<roxmltree::Error>()
+        .is_some_and(|e| matches!(e, roxmltree::Error::UnclosedRootNode))
+}
+
 #[cfg(test)]
 mod test {
     use error_stack::ResultExt;
@@ -181,7 +299,7 @@ mod test {
     use std::error::Error;
     use std::ffi::OsStr;
     use std::fs::{self, File};
-    use std::io::Read;
+    use std::io::{BufReader, Read};
 
     #[test]
     fn parse_reports() -> Result<(), Box<dyn Error>> {
@@ -212,4 +330,39 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn stream_reports() -> Result<(), Box<dyn Error>> {
+        for entry in fs::read_dir("reports")? {
+            let path = entry?.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "xml") {
+                let filename = path
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .unwrap_or("unknown");
+                let content = fs::read_to_string(&path)?;
+                let report = NmapRun::parse_and_fix(content)
+                    .attach_printable(format!("filename: {filename}"))?;
+
+                let mut stream = NmapRun::stream(BufReader::new(File::open(&path)?))
+                    .attach_printable(format!("filename: {filename}"))?;
+                let hosts = stream
+                    .by_ref()
+                    .collect::<error_stack::Result<Vec<_>, _>>()
+                    .attach_printable(format!("filename: {filename}"))?;
+
+                let mut streamed = stream.into_run();
+                streamed.hosts = Some(hosts).filter(|v| !v.is_empty());
+
+                assert_eq!(
+                    format!("{report:?}"),
+                    format!("{streamed:?}"),
+                    "filename: {filename}"
+                );
+            }
+        }
+
+        Ok(())
+    }
 }
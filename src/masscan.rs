@@ -0,0 +1,392 @@
+use error_stack::{Report, ResultExt};
+use roxmltree::Node;
+use serde::Deserialize;
+use std::{collections::HashMap, net::IpAddr};
+
+use crate::{
+    address::Address,
+    context::Context,
+    host::Host,
+    port::{Port, PortProtocol, PortState, PortStatus},
+    scaninfo::ScanInfo,
+    script::Script,
+    status::{HostState, Status},
+    Attribute, Element, Error, NmapRun, Result,
+};
+
+impl NmapRun {
+    /// Parses masscan JSON output (`-oJ`).
+    ///
+    /// Masscan writes one record per found port and one per grabbed banner,
+    /// records of the same host are merged as in masscan XML reports. The
+    /// trailing `{finished: 1}` record and the dangling commas of older masscan
+    /// versions are accepted.
+    pub fn parse_masscan_json(text: &str) -> Result<Self> {
+        let mut nmaprun = NmapRun::new("masscan");
+        let mut hosts = Vec::new();
+        let mut rest = text.trim_start().strip_prefix('[').unwrap_or(text);
+
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+            if rest.is_empty() || rest.starts_with(']') || rest.starts_with("{finished") {
+                break;
+            }
+
+            let mut records = serde_json::Deserializer::from_str(rest).into_iter::<Record>();
+            let record = records
+                .next()
+                .transpose()
+                .change_context(Error::FailedToParseMasscan)
+                .attach_printable_lazy(|| format!("record: {}", hosts.len() + 1))?;
+            rest = &rest[records.byte_offset()..];
+
+            hosts.extend(record.map(Record::into_host).transpose()?);
+        }
+
+        nmaprun.hosts = Some(merge_records(hosts)).filter(|v| !v.is_empty());
+
+        Ok(nmaprun)
+    }
+}
+
+/// A record of masscan JSON output.
+#[derive(Deserialize)]
+struct Record {
+    ip: IpAddr,
+    /// Unix time as a string, a number in some versions
+    timestamp: Option<serde_json::Value>,
+    ports: Vec<RecordPort>,
+}
+
+#[derive(Deserialize)]
+struct RecordPort {
+    port: u16,
+    proto: String,
+    status: Option<String>,
+    reason: Option<String>,
+    ttl: Option<u32>,
+    service: Option<Banner>,
+}
+
+#[derive(Deserialize)]
+struct Banner {
+    name: String,
+    banner: Option<String>,
+}
+
+impl Record {
+    fn into_host(self) -> Result<Host> {
+        let mut host = Host::new(up(), vec![Address::IpAddr(self.ip)]);
+
+        host.end_time = self.timestamp.and_then(|timestamp| match timestamp {
+            serde_json::Value::String(value) => value.parse::<u32>().ok(),
+            value => value.as_u64().and_then(|value| u32::try_from(value).ok()),
+        });
+
+        let ports = self
+            .ports
+            .into_iter()
+            .map(|port| {
+                let protocol = port
+                    .proto
+                    .parse::<PortProtocol>()
+                    .change_context(Error::FailedToParseMasscan)
+                    .attach_printable_lazy(|| format!("proto: \"{}\"", port.proto))?;
+
+                let state = port
+                    .status
+                    .as_deref()
+                    .map(str::parse::<PortState>)
+                    .transpose()
+                    .change_context(Error::FailedToParseMasscan)
+                    .attach_printable_lazy(|| format!("status: {:?}", port.status))?;
+
+                Ok(Port {
+                    protocol,
+                    port_number: port.port,
+                    status: match state {
+                        Some(state) => PortStatus {
+                            state,
+                            reason: port.reason,
+                            reason_ttl: port.ttl,
+                        },
+                        None => banner_status(),
+                    },
+                    service: None,
+                    scripts: port
+                        .service
+                        .map(|banner| vec![banner_script(banner.name, banner.banner)]),
+                    sources: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        host.ports = Some(ports).filter(|v| !v.is_empty());
+
+        Ok(host)
+    }
+}
+
+/// Parses a `<host>` of a masscan XML report.
+///
+/// Masscan hosts have no `<status>`, they are up. A grabbed banner is written
+/// as a `<service name="TYPE" banner="TEXT">` of its own host record and is
+/// kept as a script named after the banner type (`http`, `ssh`, `title`, ...).
+pub(crate) fn parse_host(node: Node, ctx: &mut Context) -> Result<Host> {
+    let end_time = node
+        .attribute("endtime")
+        .map(str::parse::<u32>)
+        .transpose()
+        .change_context(Error::FailedToParseAttribute)
+        .attach_printable(Attribute(("endtime", "host")))?;
+
+    let mut addresses = Vec::new();
+    let mut ports = Vec::new();
+
+    for child in node.children() {
+        match child.tag_name().name() {
+            "address" => addresses.extend(ctx.leaf(child, Address::parse)?),
+            "ports" => {
+                for port in child.children().filter(|n| n.has_tag_name("port")) {
+                    ports.extend(ctx.child(port, parse_port)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if addresses.is_empty() {
+        return Err(Report::new(Error::MissedElement))
+            .attach_printable(Element(("address", "host")));
+    }
+
+    let mut host = Host::new(up(), addresses);
+    host.end_time = end_time;
+    host.ports = Some(ports).filter(|v| !v.is_empty());
+
+    Ok(host)
+}
+
+fn parse_port(node: Node, ctx: &mut Context) -> Result<Port> {
+    let protocol = node
+        .attribute("protocol")
+        .ok_or(Error::MissedAttribute)
+        .attach_printable(Attribute(("protocol", "port")))
+        .and_then(|value| ctx.variant::<PortProtocol>(node, "protocol", value))
+        .attach_printable(Attribute(("protocol", "port")))?;
+
+    let port_number = node
+        .attribute("portid")
+        .ok_or(Error::MissedAttribute)
+        .attach_printable(Attribute(("portid", "port")))
+        .map(str::parse::<u16>)?
+        .change_context(Error::FailedToParseAttribute)
+        .attach_printable(Attribute(("portid", "port")))?;
+
+    let mut status = None;
+    let mut scripts = Vec::new();
+
+    for child in node.children() {
+        match child.tag_name().name() {
+            "state" => status = ctx.child(child, PortStatus::parse)?,
+            "service" => scripts.extend(ctx.leaf(child, parse_banner)?),
+            _ => {}
+        }
+    }
+
+    Ok(Port {
+        protocol,
+        port_number,
+        status: status.unwrap_or_else(banner_status),
+        service: None,
+        scripts: Some(scripts).filter(|v| !v.is_empty()),
+        sources: None,
+    })
+}
+
+/// Parses a `<service name="TYPE" banner="TEXT">` of a port.
+fn parse_banner(node: Node) -> Result<Script> {
+    let name = node
+        .attribute("name")
+        .ok_or(Error::MissedAttribute)
+        .attach_printable(Attribute(("name", "service")))?;
+    let banner = node.attribute("banner").map(str::to_string);
+
+    Ok(banner_script(name.to_string(), banner))
+}
+
+/// Parses a masscan `<scaninfo type="syn" protocol="tcp"/>`.
+///
+/// Masscan doesn't write the scanned services, `numservices` is 0 and
+/// `services` is empty.
+pub(crate) fn parse_scaninfo(node: Node, ctx: &mut Context) -> Result<ScanInfo> {
+    let ttype = node
+        .attribute("type")
+        .ok_or(Error::MissedAttribute)
+        .attach_printable(Attribute(("type", "scaninfo")))?
+        .to_string();
+
+    let protocol = node
+        .attribute("protocol")
+        .ok_or(Error::MissedAttribute)
+        .attach_printable(Attribute(("protocol", "scaninfo")))
+        .and_then(|value| ctx.variant::<PortProtocol>(node, "protocol", value))
+        .attach_printable(Attribute(("protocol", "scaninfo")))?;
+
+    Ok(ScanInfo {
+        ttype,
+        protocol,
+        numservices: 0,
+        services: String::new(),
+    })
+}
+
+/// Merges the records of one host into a single host, keeping the order in
+/// which hosts were first seen.
+///
+/// Ports with the same protocol and number are merged too: the status of a
+/// port record wins over the status of a banner record, banners are appended.
+pub(crate) fn merge_records(records: Vec<Host>) -> Vec<Host> {
+    let mut hosts: Vec<Host> = Vec::new();
+    let mut index: HashMap<Vec<Address>, usize> = HashMap::new();
+    let mut port_index: HashMap<(usize, PortProtocol, u16), usize> = HashMap::new();
+
+    for mut record in records {
+        let ports = record.ports.take();
+
+        let position = if let Some(&position) = index.get(&record.addresses) {
+            let host = &mut hosts[position];
+            host.end_time = host.end_time.max(record.end_time);
+            position
+        } else {
+            index.insert(record.addresses.clone(), hosts.len());
+            hosts.push(record);
+            hosts.len() - 1
+        };
+
+        for port in ports.into_iter().flatten() {
+            let ports = hosts[position].ports.get_or_insert_with(Vec::new);
+            let key = (position, port.protocol.clone(), port.port_number);
+            let Some(&known) = port_index.get(&key) else {
+                port_index.insert(key, ports.len());
+                ports.push(port);
+                continue;
+            };
+
+            let known = &mut ports[known];
+            if known.status.reason.is_none() && port.status.reason.is_some() {
+                known.status = port.status;
+            }
+            if let Some(scripts) = port.scripts {
+                known.scripts.get_or_insert_with(Vec::new).extend(scripts);
+            }
+        }
+    }
+
+    hosts
+}
+
+fn up() -> Status {
+    Status {
+        state: HostState::Up,
+        reason: None,
+        reason_ttl: None,
+    }
+}
+
+/// A banner record without a port status, the port answered so it's open.
+fn banner_status() -> PortStatus {
+    PortStatus {
+        state: PortState::Open,
+        reason: None,
+        reason_ttl: None,
+    }
+}
+
+fn banner_script(name: String, banner: Option<String>) -> Script {
+    Script {
+        id: name,
+        output: banner.unwrap_or_default(),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{port::PortState, Error, NmapRun, ParseOptions};
+
+    const XML: &str = r#"<?xml version="1.0"?>
+<!-- masscan v1.0 scan -->
+<nmaprun scanner="masscan" start="1490242774" version="1.0-BETA"  xmloutputversion="1.03">
+<scaninfo type="syn" protocol="tcp" />
+<host endtime="1490242774"><address addr="10.0.0.1" addrtype="ipv4"/><ports><port protocol="tcp" portid="80"><state state="open" reason="syn-ack" reason_ttl="64"/></port></ports></host>
+<host endtime="1490242776"><address addr="10.0.0.1" addrtype="ipv4"/><ports><port protocol="tcp" portid="80"><state state="open" reason="response" reason_ttl="64"/><service name="title" banner="Welcome"></service></port></ports></host>
+<host endtime="1490242775"><address addr="10.0.0.2" addrtype="ipv4"/><ports><port protocol="tcp" portid="22"><state state="open" reason="syn-ack" reason_ttl="52"/></port></ports></host>
+<runstats>
+<finished time="1490242786" timestr="2017-03-23 09:46:26" elapsed="13" />
+<hosts up="2" down="0" total="2" />
+</runstats>
+</nmaprun>
+"#;
+
+    const JSON: &str = r#"[
+{   "ip": "10.0.0.1",   "timestamp": "1490242774", "ports": [ {"port": 80, "proto": "tcp", "status": "open", "reason": "syn-ack", "ttl": 64} ] }
+,
+{   "ip": "10.0.0.1",   "timestamp": "1490242776", "ports": [ {"port": 80, "proto": "tcp", "service": {"name": "title", "banner": "Welcome"} } ] }
+,
+{   "ip": "10.0.0.2",   "timestamp": "1490242775", "ports": [ {"port": 22, "proto": "tcp", "status": "open", "reason": "syn-ack", "ttl": 52} ] }
+,
+{finished: 1}
+]
+"#;
+
+    fn check(report: &NmapRun) {
+        assert_eq!(report.scanner, "masscan");
+
+        let hosts = report.hosts.as_ref().unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].end_time, Some(1_490_242_776));
+
+        let ports = hosts[0].ports.as_ref().unwrap();
+        assert_eq!(ports.len(), 1);
+        assert_eq!(ports[0].status.state, PortState::Open);
+        assert_eq!(ports[0].status.reason.as_deref(), Some("syn-ack"));
+
+        let scripts = ports[0].scripts.as_ref().unwrap();
+        assert_eq!(
+            (scripts[0].id.as_str(), scripts[0].output.as_str()),
+            ("title", "Welcome")
+        );
+    }
+
+    #[test]
+    fn parse_masscan() {
+        let report = NmapRun::parse(XML).unwrap();
+        check(&report);
+        assert_eq!(report.scaninfos.unwrap()[0].ttype, "syn");
+        assert_eq!(report.runstats.unwrap().hosts.unwrap().up, 2);
+
+        check(&NmapRun::parse_masscan_json(JSON).unwrap());
+
+        // Records of a host can't be merged host by host
+        let e = NmapRun::stream(XML.as_bytes()).err().unwrap();
+        assert!(matches!(e.current_context(), Error::UnsupportedStream));
+        let (report, warnings) = NmapRun::recover(XML).unwrap();
+        assert!(warnings.is_empty());
+        check(&report);
+    }
+
+    #[test]
+    fn lenient_banner() {
+        let xml = XML.replace(r#"<service name="title""#, "<service");
+        assert!(NmapRun::parse(&xml).is_err());
+
+        // A broken banner is dropped, the port it was found on is kept
+        let (report, warnings) =
+            NmapRun::parse_with_options(&xml, ParseOptions { strict: false }).unwrap();
+        let ports = report.hosts.unwrap()[0].ports.clone().unwrap();
+        assert_eq!(ports[0].port_number, 80);
+        assert!(ports[0].scripts.is_none());
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,507 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::collections::{hash_map::Entry, BTreeSet, HashMap};
+use std::net::IpAddr;
+
+use crate::{
+    host::Host,
+    port::{Port, PortProtocol, Service},
+    runstats::{Finished, Hosts, RunStats},
+    scaninfo::ScanInfo,
+    script::Script,
+    status::HostState,
+    NmapRun,
+};
+
+/// A report that was merged into a run, facts point to it by index.
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SourceReport {
+    /// File the report was read from, if the caller knows it.
+    pub file: Option<String>,
+    pub scanner: String,
+    pub args: String,
+    pub start: u32,
+    pub version: String,
+}
+
+impl SourceReport {
+    /// Source of a plain report, see [`SourceReport::record_file`].
+    #[must_use]
+    pub fn of(report: &NmapRun) -> Self {
+        SourceReport {
+            file: None,
+            scanner: report.scanner.clone(),
+            args: report.args.clone(),
+            start: report.start,
+            version: report.version.clone(),
+        }
+    }
+
+    /// Records the file a report was read from before it is merged.
+    ///
+    /// A merged run keeps its `sources`, they already name the files of the
+    /// reports its facts came from.
+    pub fn record_file(report: &mut NmapRun, file: String) {
+        if report.sources.is_none() {
+            let mut source = SourceReport::of(report);
+            source.file = Some(file);
+            report.sources = Some(vec![source]);
+        }
+    }
+}
+
+impl NmapRun {
+    /// Merges reports of split scans (per subnet, per port range) into one run.
+    ///
+    /// Reports are taken from the oldest to the newest `start`, so newer facts
+    /// win. Hosts are unified by their IP address: an `up` status wins over
+    /// others, addresses, names and scripts are combined and the other host
+    /// data is taken from the newest report that has it. Ports are unified by
+    /// protocol and number: the newest state wins, the service with the highest
+    /// confidence wins, the newest on a tie. Ports of every host are sorted
+    /// by protocol and number.
+    ///
+    /// `scaninfos` of the same type and protocol are combined into one list of
+    /// services, prescripts, postscripts and events are concatenated. The
+    /// header is the one of the newest report with the earliest `start`.
+    /// `runstats` hosts are counted again from the merged hosts, hosts that
+    /// reports counted as down without listing them can't be unified and are
+    /// left out. `finished` is the latest one, without its per-report summary
+    /// and elapsed time.
+    ///
+    /// Every report is recorded in `sources`, hosts, ports and services point
+    /// to it by index. Merged runs can be merged again.
+    #[must_use]
+    pub fn merge(reports: impl IntoIterator<Item = NmapRun>) -> NmapRun {
+        let mut reports = reports.into_iter().collect::<Vec<_>>();
+        reports.sort_by_key(|report| report.start);
+
+        let start = reports.first().map_or(0, |report| report.start);
+        let mut merged = NmapRun::new("");
+        let mut sources = Vec::new();
+        let mut scaninfos = Vec::new();
+        let mut finished: Option<Finished> = None;
+        let mut hosts: Vec<Host> = Vec::new();
+        let mut index: HashMap<IpAddr, usize> = HashMap::new();
+
+        for mut report in reports {
+            let offset = sources.len();
+            sources.extend(
+                report
+                    .sources
+                    .take()
+                    .unwrap_or_else(|| vec![SourceReport::of(&report)]),
+            );
+
+            merged.scanner = report.scanner;
+            merged.args = report.args;
+            merged.version = report.version;
+            merged.xmloutputversion = report.xmloutputversion;
+
+            scaninfos.extend(report.scaninfos.into_iter().flatten());
+            extend(&mut merged.prescripts, report.prescripts);
+            extend(&mut merged.postscripts, report.postscripts);
+            extend(&mut merged.events, report.events);
+
+            if let Some(other) = report.runstats.and_then(|runstats| runstats.finished) {
+                if finished.as_ref().is_none_or(|f| f.time <= other.time) {
+                    finished = Some(other);
+                }
+            }
+
+            for mut host in report.hosts.into_iter().flatten() {
+                tag(&mut host, offset);
+
+                if let Some(&position) = host.ip().and_then(|ip| index.get(&ip)) {
+                    merge_host(&mut hosts[position], host);
+                } else {
+                    if let Some(ip) = host.ip() {
+                        index.insert(ip, hosts.len());
+                    }
+                    hosts.push(host);
+                }
+            }
+        }
+
+        for ports in hosts.iter_mut().filter_map(|host| host.ports.as_mut()) {
+            ports.sort_by_cached_key(|port| (port.protocol.to_string(), port.port_number));
+        }
+
+        let total = hosts.len();
+        let up = hosts
+            .iter()
+            .filter(|host| host.status.state == HostState::Up)
+            .count();
+        let count = |n: usize| i64::try_from(n).unwrap_or(i64::MAX);
+
+        merged.start = start;
+        merged.scaninfos = Some(merge_scaninfos(scaninfos)).filter(|v| !v.is_empty());
+        merged.runstats = Some(RunStats {
+            finished: finished.map(|finished| Finished {
+                elapsed: None,
+                summary: None,
+                ..finished
+            }),
+            hosts: Some(Hosts {
+                up: count(up),
+                down: count(total - up),
+                total: count(total),
+            }),
+        });
+        merged.hosts = Some(hosts).filter(|v| !v.is_empty());
+        merged.sources = Some(sources);
+
+        merged
+    }
+}
+
+fn extend<T>(into: &mut Option<Vec<T>>, items: Option<Vec<T>>) {
+    if let Some(items) = items {
+        into.get_or_insert_with(Vec::new).extend(items);
+    }
+}
+
+/// Points the facts of a host to its report in the merged `sources`.
+///
+/// Facts of a merged run already have indexes in its own `sources`, they are
+/// moved by `offset`. Facts of a plain report get the report's index.
+fn tag(host: &mut Host, offset: usize) {
+    let shift = |sources: &mut Option<Vec<usize>>| {
+        for index in sources.get_or_insert_with(|| vec![0]) {
+            *index += offset;
+        }
+    };
+
+    shift(&mut host.sources);
+
+    for port in host.ports.iter_mut().flatten() {
+        shift(&mut port.sources);
+        if let Some(service) = &mut port.service {
+            service.source = Some(service.source.unwrap_or(0) + offset);
+        }
+    }
+}
+
+fn merge_host(into: &mut Host, host: Host) {
+    into.start_time = into.start_time.into_iter().chain(host.start_time).min();
+    into.end_time = into.end_time.max(host.end_time);
+    into.timed_out = host.timed_out.or(into.timed_out.take());
+
+    if into.status.state != HostState::Up || host.status.state == HostState::Up {
+        into.status = host.status;
+    }
+
+    for address in host.addresses {
+        if !into.addresses.contains(&address) {
+            into.addresses.push(address);
+        }
+    }
+
+    for hostname in host.host_names.into_iter().flatten() {
+        let names = into.host_names.get_or_insert_with(Vec::new);
+        if !names
+            .iter()
+            .any(|name| name.name == hostname.name && name.ttype == hostname.ttype)
+        {
+            names.push(hostname);
+        }
+    }
+
+    if let Some(new) = host.ports {
+        let ports = into.ports.get_or_insert_with(Vec::new);
+        let mut index: HashMap<(PortProtocol, u16), usize> = ports
+            .iter()
+            .enumerate()
+            .map(|(position, port)| ((port.protocol.clone(), port.port_number), position))
+            .collect();
+
+        for port in new {
+            match index.entry((port.protocol.clone(), port.port_number)) {
+                Entry::Occupied(known) => merge_port(&mut ports[*known.get()], port),
+                Entry::Vacant(entry) => {
+                    entry.insert(ports.len());
+                    ports.push(port);
+                }
+            }
+        }
+    }
+
+    into.extraports = host.extraports.or(into.extraports.take());
+    into.os = host.os.or(into.os.take());
+    into.distance = host.distance.or(into.distance.take());
+    into.uptime = host.uptime.or(into.uptime.take());
+    into.tcp_sequence = host.tcp_sequence.or(into.tcp_sequence.take());
+    into.ip_id_sequence = host.ip_id_sequence.or(into.ip_id_sequence.take());
+    into.tcp_ts_sequence = host.tcp_ts_sequence.or(into.tcp_ts_sequence.take());
+    into.times = host.times.or(into.times.take());
+    into.trace = host.trace.or(into.trace.take());
+
+    merge_scripts(&mut into.host_scripts, host.host_scripts);
+    merge_sources(&mut into.sources, host.sources);
+}
+
+fn merge_port(into: &mut Port, port: Port) {
+    into.status = port.status;
+    into.service = match (into.service.take(), port.service) {
+        (Some(known), Some(service)) => Some(best_service(known, service)),
+        (known, service) => service.or(known),
+    };
+
+    merge_scripts(&mut into.scripts, port.scripts);
+    merge_sources(&mut into.sources, port.sources);
+}
+
+/// The service with the highest confidence, `service` is the newer one.
+/// An unknown confidence ranks below any known one.
+fn best_service(known: Service, service: Service) -> Service {
+    if service.confidence_level >= known.confidence_level {
+        service
+    } else {
+        known
+    }
+}
+
+/// Newer output of a script replaces the older one.
+fn merge_scripts(into: &mut Option<Vec<Script>>, scripts: Option<Vec<Script>>) {
+    let Some(new) = scripts else {
+        return;
+    };
+    let scripts = into.get_or_insert_with(Vec::new);
+    let mut index: HashMap<String, usize> = scripts
+        .iter()
+        .enumerate()
+        .map(|(position, script)| (script.id.clone(), position))
+        .collect();
+
+    for script in new {
+        match index.entry(script.id.clone()) {
+            Entry::Occupied(known) => scripts[*known.get()] = script,
+            Entry::Vacant(entry) => {
+                entry.insert(scripts.len());
+                scripts.push(script);
+            }
+        }
+    }
+}
+
+fn merge_sources(into: &mut Option<Vec<usize>>, sources: Option<Vec<usize>>) {
+    let mut merged = into
+        .take()
+        .into_iter()
+        .flatten()
+        .chain(sources.into_iter().flatten())
+        .collect::<Vec<_>>();
+    merged.sort_unstable();
+    merged.dedup();
+    *into = Some(merged);
+}
+
+/// Combines the services of `scaninfo`s with the same type and protocol.
+///
+/// Services that are not a list of ports and ranges are kept apart.
+fn merge_scaninfos(scaninfos: Vec<ScanInfo>) -> Vec<ScanInfo> {
+    let mut merged: Vec<ScanInfo> = Vec::new();
+
+    for scaninfo in scaninfos {
+        let same = merged.iter_mut().find(|known| {
+            known.ttype == scaninfo.ttype
+                && known.protocol == scaninfo.protocol
+                && parse_services(&known.services).is_some()
+        });
+
+        match (same, parse_services(&scaninfo.services)) {
+            (Some(known), Some(services)) => {
+                let mut all = parse_services(&known.services).unwrap_or_default();
+                all.extend(services);
+                known.numservices = u32::try_from(all.len()).unwrap_or(u32::MAX);
+                known.services = format_services(&all);
+            }
+            _ => merged.push(scaninfo),
+        }
+    }
+
+    merged
+}
+
+/// `1-1000,3389` into a set of ports.
+fn parse_services(services: &str) -> Option<BTreeSet<u32>> {
+    let mut ports = BTreeSet::new();
+
+    for item in services.split(',').filter(|item| !item.is_empty()) {
+        let (first, last) = item.split_once('-').unwrap_or((item, item));
+        let first = first.parse::<u32>().ok()?;
+        let last = last.parse::<u32>().ok()?;
+        if first > last || last > u32::from(u16::MAX) {
+            return None;
+        }
+        ports.extend(first..=last);
+    }
+
+    Some(ports)
+}
+
+fn format_services(ports: &BTreeSet<u32>) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+
+    for &port in ports {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == port => *last = port,
+            _ => ranges.push((port, port)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{first}-{last}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fixture, merge::SourceReport, status::HostState, NmapRun};
+
+    const SUBNET: &str = r#"<nmaprun scanner="nmap" args="nmap -sV -p1-1000 10.0.0.0/30" start="100" version="7.94" xmloutputversion="1.05">
+<scaninfo type="syn" protocol="tcp" numservices="1000" services="1-1000"/>
+<host><status state="up" reason="echo-reply"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="22"><state state="open"/><service name="ssh" product="OpenSSH" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="80"><state state="open"/><service name="http" method="table" conf="3"/></port>
+</ports>
+</host>
+<host><status state="down" reason="no-response"/><address addr="10.0.0.2" addrtype="ipv4"/></host>
+<runstats><finished time="110" elapsed="10"/><hosts up="1" down="3" total="4"/></runstats>
+</nmaprun>"#;
+
+    const RANGE: &str = r#"<nmaprun scanner="nmap" args="nmap -sV -p900-2000 10.0.0.1-2" start="200" version="7.95" xmloutputversion="1.05">
+<scaninfo type="syn" protocol="tcp" numservices="1101" services="900-2000"/>
+<host><status state="up" reason="echo-reply"/><address addr="10.0.0.2" addrtype="ipv4"/></host>
+<host><status state="up" reason="echo-reply"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<ports>
+<port protocol="tcp" portid="22"><state state="filtered"/><service name="ssh" method="table" conf="3"/></port>
+<port protocol="tcp" portid="80"><state state="open"/><service name="http" product="nginx" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="1723"><state state="open"/></port>
+</ports>
+</host>
+<runstats><finished time="230" elapsed="30"/><hosts up="2" down="0" total="2"/></runstats>
+</nmaprun>"#;
+
+    #[test]
+    fn merge() {
+        let reports = [RANGE, SUBNET].map(|xml| NmapRun::parse(xml).unwrap());
+        let merged = NmapRun::merge(reports);
+
+        assert_eq!((merged.start, merged.version.as_str()), (100, "7.95"));
+
+        let sources = merged.sources.as_ref().unwrap();
+        assert_eq!(sources[0].args, "nmap -sV -p1-1000 10.0.0.0/30");
+
+        let scaninfos = merged.scaninfos.as_ref().unwrap();
+        assert_eq!(scaninfos.len(), 1);
+        assert_eq!(
+            (scaninfos[0].numservices, scaninfos[0].services.as_str()),
+            (2000, "1-2000")
+        );
+
+        let hosts = merged.hosts.as_ref().unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].sources, Some(vec![0, 1]));
+        assert_eq!(hosts[1].status.state, HostState::Up);
+
+        let ports = hosts[0].ports.as_ref().unwrap();
+        assert_eq!(ports.len(), 3);
+
+        // The newest state, the most confident service
+        assert_eq!(ports[0].status.state.to_string(), "filtered");
+        let ssh = ports[0].service.as_ref().unwrap();
+        assert_eq!(
+            (ssh.product.as_deref(), ssh.source),
+            (Some("OpenSSH"), Some(0))
+        );
+        let http = ports[1].service.as_ref().unwrap();
+        assert_eq!(
+            (http.product.as_deref(), http.source),
+            (Some("nginx"), Some(1))
+        );
+        assert_eq!(ports[2].sources, Some(vec![1]));
+
+        let runstats = merged.runstats.as_ref().unwrap();
+        assert_eq!(runstats.hosts.as_ref().unwrap().up, 2);
+        assert_eq!(runstats.finished.as_ref().unwrap().time, 230);
+
+        // A merged run merges again without losing where facts came from
+        let again = NmapRun::merge([merged, NmapRun::parse(SUBNET).unwrap()]);
+        assert_eq!(again.sources.as_ref().unwrap().len(), 3);
+        let hosts = again.hosts.unwrap();
+        assert_eq!(hosts[0].sources, Some(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn merge_merged_with_files() {
+        let read = |xml: &str, file: &str| {
+            let mut report = NmapRun::parse(xml).unwrap();
+            SourceReport::record_file(&mut report, file.to_string());
+            report
+        };
+
+        let mut merged = NmapRun::merge([read(SUBNET, "subnet.xml"), read(RANGE, "range.xml")]);
+        // Read back from n2j JSON, a merged run keeps the files of its reports
+        SourceReport::record_file(&mut merged, "inventory.json".to_string());
+        let again = NmapRun::merge([merged, read(RANGE, "range-2.xml")]);
+
+        let files = again
+            .sources
+            .iter()
+            .flatten()
+            .map(|source| source.file.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![Some("subnet.xml"), Some("range.xml"), Some("range-2.xml")]
+        );
+
+        let hosts = again.hosts.unwrap();
+        let ports = hosts[0].ports.as_ref().unwrap();
+        let sources = ports
+            .iter()
+            .map(|port| (port.port_number, port.sources.clone().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            vec![(22, vec![0, 1, 2]), (80, vec![0, 1, 2]), (1723, vec![1, 2])]
+        );
+        let http = ports[1].service.as_ref().unwrap();
+        assert_eq!(http.source, Some(2));
+    }
+
+    #[test]
+    fn ports_are_sorted() {
+        // A host of a single report is sorted as a merged one
+        let xml = fixture::host(
+            r#"<ports><port protocol="udp" portid="53"><state state="open"/></port><port protocol="tcp" portid="443"><state state="open"/></port><port protocol="tcp" portid="22"><state state="open"/></port></ports>"#,
+        );
+        let merged = NmapRun::merge([NmapRun::parse(&xml).unwrap()]);
+
+        let ports = merged.hosts.unwrap()[0]
+            .ports
+            .iter()
+            .flatten()
+            .map(|port| (port.protocol.to_string(), port.port_number))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ports,
+            vec![
+                ("tcp".to_string(), 22),
+                ("tcp".to_string(), 443),
+                ("udp".to_string(), 53)
+            ]
+        );
+    }
+}
@@ -0,0 +1,532 @@
+use error_stack::ResultExt;
+use std::net::IpAddr;
+
+use crate::{
+    address::{Address, MacAddr},
+    distance::Distance,
+    gnmap::{parse_comment, parse_summary},
+    host::Host,
+    hostname::{Hostname, Type},
+    os::{Os, OsMatch},
+    port::{
+        ExtraPorts, ExtraReasons, Port, PortProtocol, PortState, PortStatus, Service, ServiceTunnel,
+    },
+    runstats::RunStats,
+    script::Script,
+    status::{HostState, Status},
+    Error, NmapRun, Result,
+};
+
+impl NmapRun {
+    /// Parses nmap normal output (`-oN`, or what nmap prints to the terminal), best effort.
+    ///
+    /// Reads `Nmap scan report for` blocks: host status, PORT tables, the MAC
+    /// address, OS details and guesses, network distance and `|` script output.
+    /// Lines that are not understood are skipped. Data the text doesn't show is
+    /// left absent instead of guessed: the confidence of services and the
+    /// accuracy of the perfect OS matches of `OS details`, which are kept as
+    /// one match. The version column is split on a best effort basis, see
+    /// `Service::set_version_column`.
+    pub fn parse_normal(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            nmaprun: NmapRun::new("nmap"),
+            hosts: Vec::new(),
+            section: Section::None,
+        };
+
+        for (index, line) in text.lines().enumerate() {
+            parser
+                .line(line.trim_end())
+                .attach_printable_lazy(|| format!("line: {}", index + 1))?;
+        }
+
+        let Parser {
+            mut nmaprun, hosts, ..
+        } = parser;
+        nmaprun.hosts = Some(hosts).filter(|v| !v.is_empty());
+
+        Ok(nmaprun)
+    }
+}
+
+/// Part of the output that `|` script lines belong to.
+enum Section {
+    None,
+    Ports(Columns),
+    HostScripts,
+    PreScripts,
+    PostScripts,
+}
+
+/// Byte offsets of the PORT table columns, taken from the table header.
+struct Columns {
+    service: usize,
+    reason: Option<usize>,
+    version: Option<usize>,
+}
+
+struct Parser {
+    nmaprun: NmapRun,
+    hosts: Vec<Host>,
+    section: Section,
+}
+
+impl Parser {
+    fn line(&mut self, line: &str) -> Result<()> {
+        if let Some(comment) = line.strip_prefix("# ") {
+            parse_comment(comment, &mut self.nmaprun);
+        } else if let Some(version) = line.strip_prefix("Starting Nmap ") {
+            if self.nmaprun.version.is_empty() {
+                let version = version.split_whitespace().next().unwrap_or_default();
+                self.nmaprun.version = version.to_string();
+            }
+        } else if let Some(summary) = line.strip_prefix("Nmap done: ") {
+            // The terminal footer has no date, `<finished>` stays absent
+            let runstats = self.nmaprun.runstats.get_or_insert_with(RunStats::default);
+            runstats.hosts = parse_summary(summary).1;
+        } else if let Some(target) = line.strip_prefix("Nmap scan report for ") {
+            self.host(target)?;
+        } else if line.starts_with('|') {
+            self.script(line);
+        } else if line.starts_with("PORT ") && line.contains(" STATE ") {
+            if let Some(service) = line.find("SERVICE") {
+                self.section = Section::Ports(Columns {
+                    service,
+                    reason: line.find("REASON"),
+                    version: line.find("VERSION"),
+                });
+            }
+        } else if line.starts_with(|c: char| c.is_ascii_digit()) && line.contains('/') {
+            if let (Section::Ports(columns), Some(host)) = (&self.section, self.hosts.last_mut()) {
+                host.ports
+                    .get_or_insert_with(Vec::new)
+                    .extend(parse_port(line, columns));
+            }
+        } else {
+            self.section = match line {
+                "Host script results:" => Section::HostScripts,
+                "Pre-scan script results:" => Section::PreScripts,
+                "Post-scan script results:" => Section::PostScripts,
+                _ => Section::None,
+            };
+
+            if let Some(host) = self.hosts.last_mut() {
+                host_line(line, host);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `scanme.nmap.org (45.33.32.156)`, `10.0.0.1` or `10.0.0.2 [host down]`
+    fn host(&mut self, target: &str) -> Result<()> {
+        let (target, state) = match target.split_once(" [host down") {
+            Some((target, _)) => (target, HostState::Down),
+            None => (target, HostState::Unknown),
+        };
+
+        let (name, addr) = match target.rsplit_once(" (") {
+            Some((name, addr)) => (Some(name), addr.trim_end_matches(')')),
+            None => (None, target),
+        };
+
+        let addr = addr
+            .parse::<IpAddr>()
+            .change_context(Error::FailedToParseNormal)
+            .attach_printable_lazy(|| format!("address: \"{addr}\""))?;
+
+        let status = Status {
+            state,
+            reason: None,
+            reason_ttl: None,
+        };
+        let mut host = Host::new(status, vec![Address::IpAddr(addr)]);
+
+        // The shown name is the target from the command line, or the PTR record
+        host.host_names = name.map(|name| {
+            let user = self.nmaprun.args.split_whitespace().any(|arg| arg == name);
+            vec![Hostname {
+                name: name.to_string(),
+                ttype: if user { Type::User } else { Type::Ptr },
+            }]
+        });
+
+        self.hosts.push(host);
+        self.section = Section::None;
+
+        Ok(())
+    }
+
+    /// `| id: first line`, `|   continuation` and the last line `|_ ...`
+    fn script(&mut self, line: &str) {
+        let rest = line
+            .strip_prefix("|_")
+            .or_else(|| line.strip_prefix('|'))
+            .unwrap_or(line);
+        let rest = rest.strip_prefix(' ').unwrap_or(rest);
+
+        let scripts = match self.section {
+            Section::Ports(_) => self
+                .hosts
+                .last_mut()
+                .and_then(|host| host.ports.as_mut())
+                .and_then(|ports| ports.last_mut())
+                .map(|port| port.scripts.get_or_insert_with(Vec::new)),
+            Section::HostScripts => self
+                .hosts
+                .last_mut()
+                .map(|host| host.host_scripts.get_or_insert_with(Vec::new)),
+            Section::PreScripts => Some(self.nmaprun.prescripts.get_or_insert_with(Vec::new)),
+            Section::PostScripts => Some(self.nmaprun.postscripts.get_or_insert_with(Vec::new)),
+            Section::None => None,
+        };
+        let Some(scripts) = scripts else {
+            return;
+        };
+
+        match script_start(rest) {
+            Some((id, output)) => scripts.push(Script {
+                id: id.to_string(),
+                output: output.to_string(),
+                data: None,
+            }),
+            None => {
+                if let Some(script) = scripts.last_mut() {
+                    script.output.push('\n');
+                    script.output.push_str(rest);
+                }
+            }
+        }
+    }
+}
+
+/// Splits `ssh-hostkey: first line` into the script id and the output,
+/// indented lines continue the previous script.
+fn script_start(line: &str) -> Option<(&str, &str)> {
+    let (id, output) = line.split_once(':')?;
+    let is_id = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    is_id.then(|| (id, output.strip_prefix(' ').unwrap_or(output)))
+}
+
+/// Lines of a host block outside of the PORT table.
+fn host_line(line: &str, host: &mut Host) {
+    if let Some(rest) = line.strip_prefix("Host is up") {
+        host.status.state = HostState::Up;
+
+        // `Host is up, received syn-ack ttl 64 (0.0012s latency).`
+        if let Some(received) = rest.strip_prefix(", received ") {
+            let mut words = received.split_whitespace();
+            host.status.reason = words
+                .next()
+                .map(|reason| reason.trim_end_matches('.').to_string());
+            if words.next() == Some("ttl") {
+                host.status.reason_ttl = words.next().and_then(|ttl| ttl.parse().ok());
+            }
+        }
+    } else if let Some((_, name)) = line
+        .strip_prefix("rDNS record for ")
+        .and_then(|rest| rest.split_once(": "))
+    {
+        let names = host.host_names.get_or_insert_with(Vec::new);
+        // With a separate rDNS line the shown name is the target from the command line
+        for hostname in names.iter_mut() {
+            hostname.ttype = Type::User;
+        }
+        names.push(Hostname {
+            name: name.to_string(),
+            ttype: Type::Ptr,
+        });
+    } else if let Some(value) = line.strip_prefix("Not shown: ") {
+        host.extraports
+            .get_or_insert_with(Vec::new)
+            .extend(parse_not_shown(value));
+    } else if let Some(extraports) = parse_all_ports(line) {
+        host.extraports
+            .get_or_insert_with(Vec::new)
+            .push(extraports);
+    } else if let Some(mac) = line.strip_prefix("MAC Address: ") {
+        let (addr, vendor) = mac.split_once(" (").unwrap_or((mac, ""));
+        let vendor = vendor.trim_end_matches(')');
+
+        if let Ok(addr) = addr.parse::<MacAddr>() {
+            host.addresses.push(Address::MacAddr {
+                addr,
+                vendor: Some(vendor.to_string()).filter(|v| !v.is_empty() && v != "Unknown"),
+            });
+        }
+    } else if let Some(details) = line.strip_prefix("OS details: ") {
+        // Names of perfect matches may contain commas, they are not split
+        os_matches(host).push(OsMatch {
+            name: details.to_string(),
+            accuracy: None,
+            line: None,
+            osclass: None,
+        });
+    } else if let Some(guesses) = line.strip_prefix("Aggressive OS guesses: ") {
+        let guesses = guesses.split("%), ").filter_map(|guess| {
+            let (name, accuracy) = guess.trim_end_matches("%)").rsplit_once(" (")?;
+            Some(OsMatch {
+                name: name.to_string(),
+                accuracy: Some(accuracy.parse::<u8>().ok().filter(|a| *a <= 100)?),
+                line: None,
+                osclass: None,
+            })
+        });
+        os_matches(host).extend(guesses);
+    } else if let Some(distance) = line.strip_prefix("Network Distance: ") {
+        host.distance = distance
+            .split_whitespace()
+            .next()
+            .and_then(|value| value.parse::<u32>().ok())
+            .map(|value| Distance { value });
+    }
+}
+
+fn os_matches(host: &mut Host) -> &mut Vec<OsMatch> {
+    &mut host.os.get_or_insert_with(Os::default).osmatch
+}
+
+/// `22/tcp  open  ssh  syn-ack ttl 64  OpenSSH 6.6.1p1 Ubuntu`, rows that
+/// can't be read are skipped.
+fn parse_port(line: &str, columns: &Columns) -> Option<Port> {
+    let mut words = line.split_whitespace();
+    let (number, protocol) = words.next()?.split_once('/')?;
+    let port_number = number.parse::<u16>().ok()?;
+    let protocol = protocol.parse::<PortProtocol>().ok()?;
+    let state = words.next()?.parse::<PortState>().ok()?;
+
+    let cell = |start: usize, end: Option<usize>| {
+        let end = end.unwrap_or(line.len()).min(line.len());
+        line.get(start..end)
+            .map(str::trim)
+            .filter(|cell| !cell.is_empty())
+    };
+
+    let service = cell(columns.service, columns.reason.or(columns.version));
+    let reason = columns
+        .reason
+        .and_then(|start| cell(start, columns.version));
+    let version = columns.version.and_then(|start| cell(start, None));
+
+    let (reason, reason_ttl) = match reason.map(|reason| reason.split_once(" ttl ")) {
+        Some(Some((reason, ttl))) => (Some(reason), ttl.parse::<u32>().ok()),
+        Some(None) => (reason, None),
+        None => (None, None),
+    };
+
+    let service = service.map(|name| {
+        let (tunnel, name) = match name.strip_prefix("ssl/") {
+            Some(name) => (Some(ServiceTunnel::Ssl), name),
+            None => (None, name),
+        };
+
+        let mut service = Service::new(name.to_string());
+        service.tunnel = tunnel;
+        if let Some(version) = version {
+            service.set_version_column(version);
+        }
+        service
+    });
+
+    Some(Port {
+        protocol,
+        port_number,
+        status: PortStatus {
+            state,
+            reason: reason.map(Into::into),
+            reason_ttl,
+        },
+        service,
+        scripts: None,
+        sources: None,
+    })
+}
+
+/// `996 closed tcp ports (reset), 2 filtered tcp ports (no-response)`,
+/// or `996 closed ports` of older versions.
+fn parse_not_shown(value: &str) -> Vec<ExtraPorts> {
+    let mut groups = Vec::new();
+    let mut depth = 0u32;
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                groups.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    groups.push(&value[start..]);
+
+    groups
+        .into_iter()
+        .filter_map(|group| {
+            let (ports, reason) = group.split_once(" (").unwrap_or((group, ""));
+            let words = ports.split_whitespace().collect::<Vec<_>>();
+
+            let (count, state, proto) = match words.as_slice() {
+                [count, state, proto, _] => (count, state, proto.parse::<PortProtocol>().ok()),
+                [count, state, _] => (count, state, None),
+                _ => return None,
+            };
+            let count = count.parse::<u32>().ok()?;
+            let state = state.parse::<PortState>().ok()?;
+
+            // Mixed reasons are written with counts in plural form, e.g.
+            // `(990 no-responses, 7 host-unreachs)`, these are not mapped
+            let reason = reason.trim_end_matches(')');
+            let extrareasons = (!reason.is_empty() && !reason.contains(' ')).then(|| {
+                vec![ExtraReasons {
+                    reason: reason.to_string(),
+                    count,
+                    proto: proto.clone(),
+                    ports: None,
+                }]
+            });
+
+            Some(ExtraPorts {
+                state,
+                count,
+                extrareasons,
+            })
+        })
+        .collect()
+}
+
+/// `All 1000 scanned ports on 10.0.0.1 are closed`
+///
+/// The `are in ignored states` form is followed by a `Not shown` line.
+fn parse_all_ports(line: &str) -> Option<ExtraPorts> {
+    let (count, rest) = line
+        .strip_prefix("All ")?
+        .split_once(" scanned ports on ")?;
+    let (_, state) = rest.rsplit_once(" are ")?;
+    let state = state.split_whitespace().next()?.trim_end_matches('.');
+
+    Some(ExtraPorts {
+        state: state.parse::<PortState>().ok()?,
+        count: count.parse::<u32>().ok()?,
+        extrareasons: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        address::Address,
+        hostname::Type,
+        port::{PortState, ServiceTunnel},
+        status::HostState,
+        NmapRun,
+    };
+
+    const NORMAL: &str = "\
+# Nmap 7.94 scan initiated Sat Jun 17 20:57:01 2023 as: nmap -sV -O --reason -oN scan.nmap scanme.nmap.org 10.0.0.2
+Nmap scan report for scanme.nmap.org (45.33.32.156)
+Host is up, received echo-reply ttl 53 (0.17s latency).
+rDNS record for 45.33.32.156: li982-156.members.linode.com
+Not shown: 996 closed tcp ports (reset)
+PORT      STATE SERVICE    REASON         VERSION
+22/tcp    open  ssh        syn-ack ttl 53 OpenSSH 6.6.1p1 Ubuntu 2ubuntu2.13 (Ubuntu Linux; protocol 2.0)
+| ssh-hostkey:
+|   1024 ac:00:a0:1a:82:ff:cc:55:99:dc:67:2b:34:97:6b:75 (DSA)
+|_  256 33:fa:91:0f:e0:e1:7b:1f:6d:05:a2:b0:f1:54:41:56 (ED25519)
+80/tcp    open  http       syn-ack ttl 53 Apache httpd 2.4.7 ((Ubuntu))
+|_http-title: Go ahead and ScanMe!
+443/tcp   open  ssl/https  syn-ack ttl 53
+9929/tcp  open  nping-echo syn-ack ttl 53 Nping echo
+MAC Address: 00:11:22:33:44:55 (Unknown)
+Aggressive OS guesses: Linux 3.10 - 4.11 (95%), Linux 3.2 - 4.9 (93%)
+Network Distance: 2 hops
+
+Host script results:
+|_clock-skew: mean: 0s
+
+Nmap scan report for 10.0.0.2 [host down, received no-response]
+# Nmap done at Sat Jun 17 20:57:15 2023 -- 2 IP addresses (1 host up) scanned in 14.12 seconds
+";
+
+    #[test]
+    fn parse_normal() {
+        let report = NmapRun::parse_normal(NORMAL).unwrap();
+        let hosts = report.hosts.as_ref().unwrap();
+        assert_eq!(hosts.len(), 2);
+
+        let host = &hosts[0];
+        assert_eq!(host.status.state, HostState::Up);
+        assert_eq!(host.status.reason_ttl, Some(53));
+        assert!(matches!(
+            host.addresses[1],
+            Address::MacAddr { vendor: None, .. }
+        ));
+
+        let names = host.host_names.as_ref().unwrap();
+        assert_eq!(
+            (&names[0].ttype, &names[1].ttype),
+            (&Type::User, &Type::Ptr)
+        );
+
+        let ports = host.ports.as_ref().unwrap();
+        assert_eq!(ports.len(), 4);
+        assert_eq!(ports[0].status.reason.as_deref(), Some("syn-ack"));
+
+        let ssh = ports[0].service.as_ref().unwrap();
+        assert_eq!(
+            (
+                ssh.product.as_deref(),
+                ssh.version.as_deref(),
+                ssh.extra_info.as_deref()
+            ),
+            (
+                Some("OpenSSH"),
+                Some("6.6.1p1 Ubuntu 2ubuntu2.13"),
+                Some("Ubuntu Linux; protocol 2.0")
+            )
+        );
+        let scripts = ports[0].scripts.as_ref().unwrap();
+        assert_eq!(scripts[0].id, "ssh-hostkey");
+        assert!(scripts[0].output.starts_with("\n  1024 ac:00"));
+
+        let https = ports[2].service.as_ref().unwrap();
+        assert_eq!(https.tunnel, Some(ServiceTunnel::Ssl));
+        assert_eq!(https.product, None);
+
+        let extraports = host.extraports.as_ref().unwrap();
+        assert_eq!(
+            (&extraports[0].state, extraports[0].count),
+            (&PortState::Closed, 996)
+        );
+
+        let os = host.os.as_ref().unwrap();
+        assert_eq!(os.best_match().unwrap().accuracy, Some(95));
+        assert_eq!(host.distance.as_ref().unwrap().value, 2);
+        assert_eq!(host.host_scripts.as_ref().unwrap()[0].id, "clock-skew");
+
+        assert_eq!(hosts[1].status.state, HostState::Down);
+        assert_eq!(report.runstats.unwrap().hosts.unwrap().total, 2);
+    }
+
+    #[test]
+    fn os_details() {
+        let text = "\
+Nmap scan report for 10.0.0.3
+Host is up (0.00050s latency).
+OS details: Linux 4.15 - 5.8, Linux 5.0 - 5.4
+";
+        let report = NmapRun::parse_normal(text).unwrap();
+        let os = report.hosts.unwrap()[0].os.clone().unwrap();
+
+        // The text doesn't show the accuracy of perfect matches
+        assert_eq!(os.osmatch.len(), 1);
+        assert_eq!(os.osmatch[0].name, "Linux 4.15 - 5.8, Linux 5.0 - 5.4");
+        assert_eq!(os.osmatch[0].accuracy, None);
+    }
+}
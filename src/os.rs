@@ -1,4 +1,5 @@
 use crate::{
+    context::Context,
     port::{PortProtocol, PortState},
     Attribute, Element, Error, Result,
 };
@@ -8,59 +9,72 @@ use serde::Deserialize;
 use serde::Serialize;
 use serde_with::skip_serializing_none;
 
+/// OS detection results of a host.
+///
+/// `osmatch` keeps the order of the report, which is nmap's own ranking
+/// (most likely match first).
+#[skip_serializing_none]
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Os {
     pub portused: Vec<PortUsed>,
     pub osmatch: Vec<OsMatch>,
-    pub osfingerprint: OsFingerprint,
+    pub osfingerprint: Option<OsFingerprint>,
 }
 
 impl Os {
-    pub fn parse(node: Node) -> Result<Os> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Os> {
         let mut os = Os::default();
         for child in node.children() {
             match child.tag_name().name() {
-                "portused" => os.portused.push(PortUsed::parse(child)?),
-                "osmatch" => os.osmatch.push(OsMatch::parse(child)?),
-                "osfingerprint" => os.osfingerprint = OsFingerprint::parse(child)?,
+                "portused" => os.portused.extend(ctx.child(child, PortUsed::parse)?),
+                "osmatch" => os.osmatch.extend(ctx.child(child, OsMatch::parse)?),
+                "osfingerprint" => os.osfingerprint = ctx.leaf(child, OsFingerprint::parse)?,
                 _ => {}
             }
         }
 
         Ok(os)
     }
+
+    /// Best guess OS: the match with the highest accuracy, ties resolved by nmap's ranking.
+    /// A match of unknown accuracy ranks below any known one.
+    #[must_use]
+    pub fn best_match(&self) -> Option<&OsMatch> {
+        self.osmatch
+            .iter()
+            .rev()
+            .max_by_key(|osmatch| osmatch.accuracy)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortUsed {
     pub state: PortState,
     pub proto: PortProtocol,
-    pub portid: i64,
+    pub portid: u16,
 }
 
 impl PortUsed {
-    pub fn parse(node: Node) -> Result<PortUsed> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<PortUsed> {
         let state = node
             .attribute("state")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("state", "portused")))
-            .map(str::parse::<PortState>)?
-            .change_context(Error::FailedToParseAttribute)
+            .and_then(|value| ctx.variant::<PortState>(node, "state", value))
             .attach_printable(Attribute(("state", "portused")))?;
 
         let proto = node
             .attribute("proto")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("proto", "portused")))
-            .map(str::parse::<PortProtocol>)?
-            .change_context(Error::FailedToParseAttribute)
+            .and_then(|value| ctx.variant::<PortProtocol>(node, "proto", value))
             .attach_printable(Attribute(("proto", "portused")))?;
 
         let portid = node
             .attribute("portid")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("portid", "portused")))
-            .map(str::parse::<i64>)?
+            .map(str::parse::<u16>)?
             .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("portid", "portused")))?;
 
@@ -76,13 +90,14 @@ impl PortUsed {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OsMatch {
     pub name: String,
-    pub accuracy: i64,
-    pub line: Option<i64>,
+    /// Percentage, absent when the source format doesn't show it.
+    pub accuracy: Option<u8>,
+    pub line: Option<u32>,
     pub osclass: Option<Vec<OsClass>>,
 }
 
 impl OsMatch {
-    pub fn parse(node: Node) -> Result<OsMatch> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<OsMatch> {
         let name = node
             .attribute("name")
             .ok_or(Error::MissedAttribute)
@@ -95,13 +110,15 @@ impl OsMatch {
             .attribute("accuracy")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("accuracy", "osmatch")))
-            .map(str::parse::<i64>)?
+            .map(str::parse::<u8>)?
             .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("accuracy", "osmatch")))
+            .and_then(check_accuracy)
             .attach_printable(Attribute(("accuracy", "osmatch")))?;
 
         let line = node
             .attribute("line")
-            .map(str::parse::<i64>)
+            .map(str::parse::<u32>)
             .transpose()
             .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("line", "osmatch")))?;
@@ -112,7 +129,7 @@ impl OsMatch {
             #[allow(clippy::single_match)]
             match child.tag_name().name() {
                 "osclass" => {
-                    osclass.push(OsClass::parse(child)?);
+                    osclass.extend(ctx.child(child, OsClass::parse)?);
                 }
                 _ => {}
             }
@@ -120,7 +137,7 @@ impl OsMatch {
 
         Ok(OsMatch {
             name,
-            accuracy,
+            accuracy: Some(accuracy),
             line,
             osclass: Some(osclass).filter(|v| !v.is_empty()),
         })
@@ -135,12 +152,12 @@ pub struct OsClass {
     pub vendor: String,
     pub osfamily: String,
     pub osgen: Option<String>,
-    pub accuracy: i64,
+    pub accuracy: u8,
     pub cpe: Option<Vec<Cpe>>,
 }
 
 impl OsClass {
-    pub fn parse(node: Node) -> Result<OsClass> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<OsClass> {
         let ttype = node
             .attribute("type")
             .ok_or(Error::MissedAttribute)
@@ -165,8 +182,10 @@ impl OsClass {
             .attribute("accuracy")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("accuracy", "osclass")))
-            .map(str::parse::<i64>)?
+            .map(str::parse::<u8>)?
             .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("accuracy", "osclass")))
+            .and_then(check_accuracy)
             .attach_printable(Attribute(("accuracy", "osclass")))?;
 
         let mut cpe = Vec::new();
@@ -174,7 +193,7 @@ impl OsClass {
         for child in node.children() {
             #[allow(clippy::single_match)]
             match child.tag_name().name() {
-                "cpe" => cpe.push(Cpe::parse(child)?),
+                "cpe" => cpe.extend(ctx.leaf(child, Cpe::parse)?),
                 _ => {}
             }
         }
@@ -190,8 +209,8 @@ impl OsClass {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Cpe(String);
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cpe(pub String);
 
 impl Cpe {
     pub fn parse(node: Node) -> Result<Cpe> {
@@ -205,7 +224,7 @@ impl Cpe {
     }
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OsFingerprint {
     pub fingerprint: String,
 }
@@ -223,3 +242,60 @@ impl OsFingerprint {
         Ok(OsFingerprint { fingerprint })
     }
 }
+
+/// Accuracy is a percentage, anything above 100 is a broken report.
+fn check_accuracy(accuracy: u8) -> Result<u8> {
+    if accuracy > 100 {
+        return Err(error_stack::Report::new(Error::FailedToParseAttribute))
+            .attach_printable(format!("accuracy out of range: {accuracy}"));
+    }
+    Ok(accuracy)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fixture, os::Cpe, Error, NmapRun};
+
+    fn report(os: &str) -> String {
+        fixture::host(&format!("<os>{os}</os>"))
+    }
+
+    #[test]
+    fn os_matches() {
+        let xml = report(
+            r#"<portused state="open" proto="tcp" portid="22"/>
+<osmatch name="Linux 4.15 - 5.8" accuracy="96" line="67890">
+<osclass type="general purpose" vendor="Linux" osfamily="Linux" osgen="4.X" accuracy="96"><cpe>cpe:/o:linux:linux_kernel:4</cpe></osclass>
+<osclass type="general purpose" vendor="Linux" osfamily="Linux" osgen="5.X" accuracy="96"><cpe>cpe:/o:linux:linux_kernel:5</cpe></osclass>
+</osmatch>
+<osmatch name="Linux 5.0 - 5.14" accuracy="98" line="68000"/>
+<osmatch name="Linux 2.6.32" accuracy="98" line="12345"/>"#,
+        );
+        let report = NmapRun::parse(&xml).unwrap();
+        let os = report.hosts.unwrap()[0].os.clone().unwrap();
+
+        assert_eq!(os.portused[0].portid, 22);
+        assert_eq!(os.osmatch.len(), 3);
+        assert_eq!(os.osmatch[0].accuracy, Some(96));
+        assert_eq!(os.osmatch[0].line, Some(67890));
+        let classes = os.osmatch[0].osclass.as_ref().unwrap();
+        assert_eq!(classes[1].osgen.as_deref(), Some("5.X"));
+        assert_eq!(classes[1].accuracy, 96);
+        assert_eq!(
+            classes[0].cpe.as_deref(),
+            Some(&[Cpe("cpe:/o:linux:linux_kernel:4".to_string())][..])
+        );
+        assert!(os.osmatch[1].osclass.is_none());
+
+        // Ties go to the match nmap listed first
+        assert_eq!(os.best_match().unwrap().name, "Linux 5.0 - 5.14");
+    }
+
+    #[test]
+    fn accuracy_out_of_range() {
+        let xml = report(r#"<osmatch name="Linux" accuracy="101" line="1"/>"#);
+        let e = NmapRun::parse(&xml).unwrap_err();
+        assert!(matches!(e.current_context(), Error::FailedToParseAttribute));
+        assert!(format!("{e:?}").contains("accuracy out of range: 101"));
+    }
+}
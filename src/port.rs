@@ -2,10 +2,15 @@ use error_stack::ResultExt;
 use roxmltree::Node;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
-use std::str::FromStr;
+use std::fmt;
 use strum_macros::EnumString;
 
-use crate::{Attribute, Error, Result};
+use crate::{
+    context::{Context, OtherVariant},
+    os::Cpe,
+    script::Script,
+    Attribute, Element, Error, Result,
+};
 
 #[skip_serializing_none]
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -15,14 +20,14 @@ pub struct Ports {
 }
 
 impl Ports {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let mut ports = Vec::new();
         let mut extraports = Vec::new();
 
         for child in node.children() {
             match child.tag_name().name() {
-                "port" => ports.push(Port::parse(child)?),
-                "extraports" => extraports.push(ExtraPorts::parse(child)?),
+                "port" => ports.extend(ctx.child(child, Port::parse)?),
+                "extraports" => extraports.extend(ctx.child(child, ExtraPorts::parse)?),
                 _ => {}
             }
         }
@@ -43,13 +48,12 @@ pub struct ExtraPorts {
 }
 
 impl ExtraPorts {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let state = node
             .attribute("state")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("state", "extraports")))
-            .map(PortState::from_str)?
-            .change_context(Error::FailedToParseAttribute)
+            .and_then(|value| ctx.variant::<PortState>(node, "state", value))
             .attach_printable(Attribute(("state", "extraports")))?;
 
         let count = node
@@ -65,7 +69,7 @@ impl ExtraPorts {
         for child in node.children() {
             #[allow(clippy::single_match)]
             match child.tag_name().name() {
-                "extrareasons" => extrareasons.push(ExtraReasons::parse(child)?),
+                "extrareasons" => extrareasons.extend(ctx.child(child, ExtraReasons::parse)?),
                 _ => {}
             }
         }
@@ -88,7 +92,7 @@ pub struct ExtraReasons {
 }
 
 impl ExtraReasons {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let reason = node
             .attribute("reason")
             .ok_or(Error::MissedAttribute)
@@ -105,9 +109,8 @@ impl ExtraReasons {
 
         let proto = node
             .attribute("proto")
-            .map(str::parse::<PortProtocol>)
+            .map(|value| ctx.variant::<PortProtocol>(node, "proto", value))
             .transpose()
-            .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("proto", "extrareasons")))?;
 
         let ports = node.attribute("ports").map(Into::into);
@@ -128,16 +131,18 @@ pub struct Port {
     pub port_number: u16,
     pub status: PortStatus,
     pub service: Option<Service>,
+    pub scripts: Option<Vec<Script>>,
+    /// Indexes in `NmapRun::sources` of the merged reports that listed the port.
+    pub sources: Option<Vec<usize>>,
 }
 
 impl Port {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let protocol = node
             .attribute("protocol")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("protocol", "port")))
-            .map(str::parse::<PortProtocol>)?
-            .change_context(Error::FailedToParseAttribute)
+            .and_then(|value| ctx.variant::<PortProtocol>(node, "protocol", value))
             .attach_printable(Attribute(("protocol", "port")))?;
 
         let port_number = node
@@ -150,11 +155,13 @@ impl Port {
 
         let mut status = None;
         let mut service = None;
+        let mut scripts = Vec::new();
 
         for child in node.children() {
             match child.tag_name().name() {
-                "state" => status = Some(PortStatus::parse(child)?),
-                "service" => service = Some(Service::parse(child)?),
+                "state" => status = ctx.child(child, PortStatus::parse)?,
+                "service" => service = ctx.child(child, Service::parse)?,
+                "script" => scripts.extend(ctx.leaf(child, Script::parse)?),
                 _ => {}
             }
         }
@@ -168,11 +175,19 @@ impl Port {
             port_number,
             status,
             service,
+            scripts: Some(scripts).filter(|v| !v.is_empty()),
+            sources: None,
         })
     }
+
+    /// `open` or `open|filtered`, a port that may accept connections.
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        matches!(self.status.state, PortState::Open | PortState::OpenFiltered)
+    }
 }
 
-#[derive(EnumString, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(EnumString, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
 pub enum PortProtocol {
@@ -180,6 +195,27 @@ pub enum PortProtocol {
     Tcp,
     Udp,
     Sctp,
+    #[strum(disabled)]
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl OtherVariant for PortProtocol {
+    fn other(value: String) -> Self {
+        PortProtocol::Other(value)
+    }
+}
+
+impl fmt::Display for PortProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PortProtocol::Ip => "ip",
+            PortProtocol::Tcp => "tcp",
+            PortProtocol::Udp => "udp",
+            PortProtocol::Sctp => "sctp",
+            PortProtocol::Other(value) => value,
+        })
+    }
 }
 
 #[skip_serializing_none]
@@ -191,13 +227,12 @@ pub struct PortStatus {
 }
 
 impl PortStatus {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let state = node
             .attribute("state")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("state", "portstatus")))
-            .map(str::parse::<PortState>)?
-            .change_context(Error::FailedToParseAttribute)
+            .and_then(|value| ctx.variant::<PortState>(node, "state", value))
             .attach_printable(Attribute(("state", "portstatus")))?;
 
         let reason = node.attribute("reason").map(str::to_string);
@@ -230,6 +265,30 @@ pub enum PortState {
     #[strum(serialize = "closed|filtered")]
     ClosedFiltered,
     Unknown,
+    #[strum(disabled)]
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl OtherVariant for PortState {
+    fn other(value: String) -> Self {
+        PortState::Other(value)
+    }
+}
+
+impl fmt::Display for PortState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+            PortState::Unfiltered => "unfiltered",
+            PortState::OpenFiltered => "open|filtered",
+            PortState::ClosedFiltered => "closed|filtered",
+            PortState::Unknown => "unknown",
+            PortState::Other(value) => value,
+        })
+    }
 }
 
 #[skip_serializing_none]
@@ -237,13 +296,28 @@ pub enum PortState {
 pub struct Service {
     pub name: String,
     pub product: Option<String>,
+    pub version: Option<String>,
     pub extra_info: Option<String>,
-    pub confidence_level: u8,
+    pub os_type: Option<String>,
+    pub device_type: Option<String>,
+    pub hostname: Option<String>,
+    pub tunnel: Option<ServiceTunnel>,
+    pub proto: Option<ServiceProto>,
+    pub rpc_num: Option<u32>,
+    pub low_ver: Option<u32>,
+    pub high_ver: Option<u32>,
+    /// Nmap's confidence in the detection, 0 to 10, absent when the source
+    /// format doesn't record it or a leniently parsed `<service>` lacks it.
+    pub confidence_level: Option<u8>,
     pub method: Option<ServiceMethod>,
+    pub service_fp: Option<String>,
+    pub cpe: Option<Vec<Cpe>>,
+    /// Index in `NmapRun::sources` of the merged report the service was detected in.
+    pub source: Option<usize>,
 }
 
 impl Service {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let name = node
             .attribute("name")
             .ok_or(Error::MissedAttribute)
@@ -257,6 +331,8 @@ impl Service {
             .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("product", "service")))?;
 
+        let version = node.attribute("version").map(Into::into);
+
         let extra_info = node
             .attribute("extrainfo")
             .map(str::parse::<String>)
@@ -264,29 +340,166 @@ impl Service {
             .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("extrainfo", "service")))?;
 
-        let confidence_level = node
-            .attribute("conf")
-            .ok_or(Error::MissedAttribute)
-            .attach_printable(Attribute(("conf", "service")))
-            .map(str::parse::<u8>)?
+        let os_type = node.attribute("ostype").map(Into::into);
+        let device_type = node.attribute("devicetype").map(Into::into);
+        let hostname = node.attribute("hostname").map(Into::into);
+
+        let tunnel = node
+            .attribute("tunnel")
+            .map(|value| ctx.variant::<ServiceTunnel>(node, "tunnel", value))
+            .transpose()
+            .attach_printable(Attribute(("tunnel", "service")))?;
+
+        let proto = node
+            .attribute("proto")
+            .map(|value| ctx.variant::<ServiceProto>(node, "proto", value))
+            .transpose()
+            .attach_printable(Attribute(("proto", "service")))?;
+
+        let rpc_num = node
+            .attribute("rpcnum")
+            .map(str::parse::<u32>)
+            .transpose()
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("rpcnum", "service")))?;
+
+        let low_ver = node
+            .attribute("lowver")
+            .map(str::parse::<u32>)
+            .transpose()
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("lowver", "service")))?;
+
+        let high_ver = node
+            .attribute("highver")
+            .map(str::parse::<u32>)
+            .transpose()
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("highver", "service")))?;
+
+        let confidence_level = ctx
+            .required(node, "conf")
+            .attach_printable(Attribute(("conf", "service")))?
+            .map(str::parse::<u8>)
+            .transpose()
             .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("conf", "service")))?;
 
         let method = node
             .attribute("method")
-            .map(str::parse::<ServiceMethod>)
+            .map(|value| ctx.variant::<ServiceMethod>(node, "method", value))
             .transpose()
-            .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("method", "service")))?;
 
+        let service_fp = node.attribute("servicefp").map(Into::into);
+
+        let mut cpe = Vec::new();
+
+        for child in node.children() {
+            #[allow(clippy::single_match)]
+            match child.tag_name().name() {
+                "cpe" => cpe.push(Cpe::parse(child).attach_printable(Element(("cpe", "service")))?),
+                _ => {}
+            }
+        }
+
         Ok(Service {
             name,
             product,
+            version,
             extra_info,
+            os_type,
+            device_type,
+            hostname,
+            tunnel,
+            proto,
+            rpc_num,
+            low_ver,
+            high_ver,
             confidence_level,
             method,
+            service_fp,
+            cpe: Some(cpe).filter(|v| !v.is_empty()),
+            source: None,
         })
     }
+
+    /// Service known only by name, as the source format doesn't record how
+    /// it was detected.
+    pub(crate) fn new(name: String) -> Self {
+        Service {
+            name,
+            product: None,
+            version: None,
+            extra_info: None,
+            os_type: None,
+            device_type: None,
+            hostname: None,
+            tunnel: None,
+            proto: None,
+            rpc_num: None,
+            low_ver: None,
+            high_ver: None,
+            confidence_level: None,
+            method: None,
+            service_fp: None,
+            cpe: None,
+            source: None,
+        }
+    }
+
+    /// Fills product, version and extra info from the version column of text
+    /// reports, which nmap writes as `product version (extra info)`.
+    ///
+    /// Best effort: the version starts at the first word beginning with a
+    /// digit, e.g. `Apache httpd 2.4.57 ((Debian))` has the product
+    /// `Apache httpd`, the version `2.4.57` and the extra info `(Debian)`.
+    pub(crate) fn set_version_column(&mut self, column: &str) {
+        let mut rest = column.trim();
+
+        if let Some(open) = extra_info_start(rest) {
+            let extra_info = &rest[open + 1..rest.len() - 1];
+            self.extra_info = Some(extra_info.to_string()).filter(|v| !v.is_empty());
+            rest = rest[..open].trim_end();
+        }
+
+        let version = rest
+            .char_indices()
+            .filter(|(i, _)| *i == 0 || rest[..*i].ends_with(' '))
+            .find(|(_, c)| c.is_ascii_digit())
+            .map(|(i, _)| i);
+
+        let (product, version) = match version {
+            Some(i) => (rest[..i].trim_end(), Some(&rest[i..])),
+            None => (rest, None),
+        };
+
+        self.product = Some(product.to_string()).filter(|v| !v.is_empty());
+        self.version = version.map(Into::into);
+    }
+}
+
+/// Byte index of the `(` that opens the parenthesized end of `column`.
+fn extra_info_start(column: &str) -> Option<usize> {
+    if !column.ends_with(')') {
+        return None;
+    }
+
+    let mut depth = 0;
+    for (i, c) in column.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
 }
 
 #[derive(EnumString, Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -296,4 +509,199 @@ pub enum ServiceMethod {
     Table,
     Probed,
     Detection,
+    #[strum(disabled)]
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl OtherVariant for ServiceMethod {
+    fn other(value: String) -> Self {
+        ServiceMethod::Other(value)
+    }
+}
+
+impl fmt::Display for ServiceMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ServiceMethod::Table => "table",
+            ServiceMethod::Probed => "probed",
+            ServiceMethod::Detection => "detection",
+            ServiceMethod::Other(value) => value,
+        })
+    }
+}
+
+#[derive(EnumString, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ServiceTunnel {
+    Ssl,
+    #[strum(disabled)]
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl OtherVariant for ServiceTunnel {
+    fn other(value: String) -> Self {
+        ServiceTunnel::Other(value)
+    }
+}
+
+impl fmt::Display for ServiceTunnel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ServiceTunnel::Ssl => "ssl",
+            ServiceTunnel::Other(value) => value,
+        })
+    }
+}
+
+#[derive(EnumString, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ServiceProto {
+    Rpc,
+    #[strum(disabled)]
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl OtherVariant for ServiceProto {
+    fn other(value: String) -> Self {
+        ServiceProto::Other(value)
+    }
+}
+
+impl fmt::Display for ServiceProto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ServiceProto::Rpc => "rpc",
+            ServiceProto::Other(value) => value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fixture,
+        os::Cpe,
+        port::{Port, PortState, Service, ServiceMethod, ServiceProto, ServiceTunnel},
+        NmapRun,
+    };
+
+    fn ports(ports: &str) -> Vec<Port> {
+        let xml = fixture::host(&format!("<ports>{ports}</ports>"));
+        let report = NmapRun::parse(&xml).unwrap();
+        report.hosts.unwrap().remove(0).ports.unwrap()
+    }
+
+    #[test]
+    fn port_scripts() {
+        let ports = ports(
+            r#"<port protocol="tcp" portid="80"><state state="open" reason="syn-ack" reason_ttl="64"/>
+<script id="http-title" output="Welcome"><elem key="title">Welcome</elem></script>
+<script id="http-server-header" output="nginx"/>
+</port>
+<port protocol="tcp" portid="81"><state state="closed" reason="reset"/></port>"#,
+        );
+
+        assert_eq!(ports[0].status.state, PortState::Open);
+        assert_eq!(ports[0].status.reason_ttl, Some(64));
+
+        let scripts = ports[0].scripts.as_ref().unwrap();
+        let ids = scripts.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["http-title", "http-server-header"]);
+        assert_eq!(scripts[0].output, "Welcome");
+        assert_eq!(scripts[1].output, "nginx");
+
+        assert!(ports[1].scripts.is_none());
+    }
+
+    #[test]
+    fn service_attributes() {
+        let ports = ports(
+            r#"<port protocol="tcp" portid="443"><state state="open"/>
+<service name="http" product="nginx" version="1.24.0" extrainfo="Ubuntu" ostype="Linux" devicetype="proxy server" hostname="www.lan" tunnel="ssl" method="probed" conf="10" servicefp="SF-Port443-TCP:V=7.94">
+<cpe>cpe:/a:igor_sysoev:nginx:1.24.0</cpe><cpe>cpe:/o:linux:linux_kernel</cpe>
+</service></port>
+<port protocol="tcp" portid="111"><state state="open"/>
+<service name="rpcbind" proto="rpc" rpcnum="100000" lowver="2" highver="4" method="table" conf="3"/></port>"#,
+        );
+
+        let https = ports[0].service.as_ref().unwrap();
+        assert_eq!(https.name, "http");
+        assert_eq!(https.product.as_deref(), Some("nginx"));
+        assert_eq!(https.version.as_deref(), Some("1.24.0"));
+        assert_eq!(https.extra_info.as_deref(), Some("Ubuntu"));
+        assert_eq!(https.os_type.as_deref(), Some("Linux"));
+        assert_eq!(https.device_type.as_deref(), Some("proxy server"));
+        assert_eq!(https.hostname.as_deref(), Some("www.lan"));
+        assert_eq!(https.tunnel, Some(ServiceTunnel::Ssl));
+        assert_eq!(https.method, Some(ServiceMethod::Probed));
+        assert_eq!(https.confidence_level, Some(10));
+        assert_eq!(https.service_fp.as_deref(), Some("SF-Port443-TCP:V=7.94"));
+        assert_eq!(
+            https.cpe.as_deref(),
+            Some(
+                &[
+                    Cpe("cpe:/a:igor_sysoev:nginx:1.24.0".to_string()),
+                    Cpe("cpe:/o:linux:linux_kernel".to_string())
+                ][..]
+            )
+        );
+
+        let rpc = ports[1].service.as_ref().unwrap();
+        assert_eq!(rpc.proto, Some(ServiceProto::Rpc));
+        assert_eq!(
+            (rpc.rpc_num, rpc.low_ver, rpc.high_ver),
+            (Some(100_000), Some(2), Some(4))
+        );
+        assert_eq!(rpc.method, Some(ServiceMethod::Table));
+        assert!(rpc.cpe.is_none() && rpc.tunnel.is_none());
+    }
+
+    #[test]
+    fn version_column() {
+        for (column, product, version, extra_info) in [
+            (
+                "OpenSSH 8.4 (protocol 2.0)",
+                Some("OpenSSH"),
+                Some("8.4"),
+                Some("protocol 2.0"),
+            ),
+            (
+                "Apache httpd 2.4.57 ((Debian))",
+                Some("Apache httpd"),
+                Some("2.4.57"),
+                Some("(Debian)"),
+            ),
+            (
+                "OpenSSH 7.4p1 Debian 10+deb9u7",
+                Some("OpenSSH"),
+                Some("7.4p1 Debian 10+deb9u7"),
+                None,
+            ),
+            (
+                "Microsoft Windows RPC",
+                Some("Microsoft Windows RPC"),
+                None,
+                None,
+            ),
+            ("(protocol 2.0)", None, None, Some("protocol 2.0")),
+            ("", None, None, None),
+        ] {
+            let mut service = Service::new("test".to_string());
+            service.set_version_column(column);
+            assert_eq!(
+                (
+                    service.product.as_deref(),
+                    service.version.as_deref(),
+                    service.extra_info.as_deref()
+                ),
+                (product, version, extra_info),
+                "{column}"
+            );
+        }
+    }
 }
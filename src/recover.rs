@@ -0,0 +1,106 @@
+use crate::{
+    masscan,
+    warning::{describe, Warning},
+    NmapRun, Result,
+};
+
+impl NmapRun {
+    /// Parses as much of a truncated or corrupted report as possible.
+    ///
+    /// Every complete `<host>` before the damage point is kept, dropped elements
+    /// are reported as warnings. Fails only if the `<nmaprun>` header is unreadable.
+    pub fn recover(xml: &str) -> Result<(Self, Vec<Warning>)> {
+        let mut stream = NmapRun::stream_records(xml.as_bytes())?;
+        let mut hosts = Vec::new();
+        let mut warnings = Vec::new();
+
+        while let Some(host) = stream.next() {
+            match host {
+                Ok(host) => hosts.push(host),
+                Err(e) => {
+                    let (element, offset) = stream
+                        .last_element()
+                        .map(|(name, offset)| (name.to_string(), offset))
+                        .unzip();
+
+                    let address = match (&element, offset) {
+                        (Some(element), Some(offset)) if element == "host" => {
+                            usize::try_from(offset)
+                                .ok()
+                                .and_then(|offset| xml.get(offset..))
+                                .and_then(partial_address)
+                        }
+                        _ => None,
+                    };
+
+                    warnings.push(Warning {
+                        message: describe(&e),
+                        element,
+                        offset,
+                        address,
+                        location: None,
+                    });
+
+                    if stream.is_done() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut nmaprun = stream.into_run();
+        if nmaprun.scanner == "masscan" {
+            hosts = masscan::merge_records(hosts);
+        }
+        nmaprun.hosts = Some(hosts).filter(|v| !v.is_empty());
+
+        Ok((nmaprun, warnings))
+    }
+}
+
+/// Finds the `addr` attribute of the first `<address>` of a (possibly cut) host.
+fn partial_address(host: &str) -> Option<String> {
+    let end = host.find("</host>").unwrap_or(host.len());
+    let host = &host[..end];
+
+    let tag = &host[host.find("<address")?..];
+    let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+
+    let (index, _) = tag
+        .match_indices("addr=")
+        .find(|(index, _)| tag[..*index].ends_with(char::is_whitespace))?;
+    let value = &tag[index + "addr=".len()..];
+    let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let value = &value[1..];
+
+    value.find(quote).map(|end| value[..end].to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::NmapRun;
+    use std::error::Error;
+    use std::fs;
+
+    #[test]
+    fn recover_truncated_host() -> Result<(), Box<dyn Error>> {
+        let xml = fs::read_to_string("reports/nmap_example.xml")?;
+
+        // Cut the report in the middle of the third host, right after its address
+        let (third, _) = xml.match_indices("<host>").nth(2).ok_or("no third host")?;
+        let address = third + xml[third..].find("<address addr=\"").ok_or("no address")?;
+        let value = address + "<address addr=\"".len();
+        let value = &xml[value..value + xml[value..].find('"').ok_or("no quote")?];
+        let cut = address + 40;
+
+        let (report, warnings) = NmapRun::recover(&xml[..cut])?;
+
+        assert_eq!(report.hosts.map(|hosts| hosts.len()), Some(2));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].element.as_deref(), Some("host"));
+        assert_eq!(warnings[0].offset, u64::try_from(third).ok());
+        assert_eq!(warnings[0].address.as_deref(), Some(value));
+
+        Ok(())
+    }
+}
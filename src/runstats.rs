@@ -2,8 +2,13 @@ use error_stack::ResultExt;
 use roxmltree::Node;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
+use std::fmt;
+use strum_macros::EnumString;
 
-use crate::{Attribute, Error, Result};
+use crate::{
+    context::{Context, OtherVariant},
+    Attribute, Error, Result,
+};
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -13,12 +18,12 @@ pub struct RunStats {
 }
 
 impl RunStats {
-    pub fn parse(node: Node) -> Result<RunStats> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<RunStats> {
         let mut runstats = RunStats::default();
         for child in node.children() {
             match child.tag_name().name() {
-                "finished" => runstats.finished = Finished::parse(child).map(Option::Some)?,
-                "hosts" => runstats.hosts = Hosts::parse(child).map(Option::Some)?,
+                "finished" => runstats.finished = ctx.child(child, Finished::parse)?,
+                "hosts" => runstats.hosts = ctx.leaf(child, Hosts::parse)?,
                 _ => {}
             }
         }
@@ -31,11 +36,15 @@ impl RunStats {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Finished {
     pub time: i64,
+    pub timestr: Option<String>,
     pub elapsed: Option<f64>,
+    pub summary: Option<String>,
+    pub exit: Option<Exit>,
+    pub errormsg: Option<String>,
 }
 
 impl Finished {
-    pub fn parse(node: Node) -> Result<Finished> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Finished> {
         let time = node
             .attribute("time")
             .ok_or(Error::MissedAttribute)
@@ -44,6 +53,8 @@ impl Finished {
             .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("time", "finished")))?;
 
+        let timestr = node.attribute("timestr").map(Into::into);
+
         let elapsed = node
             .attribute("elapsed")
             .map(str::parse::<f64>)
@@ -51,7 +62,51 @@ impl Finished {
             .change_context(Error::FailedToParseAttribute)
             .attach_printable(Attribute(("elapsed", "finished")))?;
 
-        Ok(Finished { time, elapsed })
+        let summary = node.attribute("summary").map(Into::into);
+
+        let exit = node
+            .attribute("exit")
+            .map(|value| ctx.variant::<Exit>(node, "exit", value))
+            .transpose()
+            .attach_printable(Attribute(("exit", "finished")))?;
+
+        let errormsg = node.attribute("errormsg").map(Into::into);
+
+        Ok(Finished {
+            time,
+            timestr,
+            elapsed,
+            summary,
+            exit,
+            errormsg,
+        })
+    }
+}
+
+#[derive(EnumString, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Exit {
+    Success,
+    Error,
+    #[strum(disabled)]
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl OtherVariant for Exit {
+    fn other(value: String) -> Self {
+        Exit::Other(value)
+    }
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Exit::Success => "success",
+            Exit::Error => "error",
+            Exit::Other(value) => value,
+        })
     }
 }
 
@@ -91,3 +146,70 @@ impl Hosts {
         Ok(Hosts { up, down, total })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fixture,
+        runstats::{Exit, Hosts},
+        NmapRun,
+    };
+
+    fn report(finished: &str) -> String {
+        fixture::run(&format!(
+            r#"<runstats>{finished}<hosts up="1" down="3" total="4"/></runstats>"#
+        ))
+    }
+
+    #[test]
+    fn finished() {
+        let xml = report(
+            r#"<finished time="1700000042" timestr="Tue Nov 14 22:14:02 2023" elapsed="41.86" summary="Nmap done at Tue Nov 14 22:14:02 2023; 4 IP addresses (1 host up) scanned in 41.86 seconds" exit="success"/>"#,
+        );
+        let runstats = NmapRun::parse(&xml).unwrap().runstats.unwrap();
+
+        let finished = runstats.finished.unwrap();
+        assert_eq!(finished.time, 1_700_000_042);
+        assert_eq!(
+            finished.timestr.as_deref(),
+            Some("Tue Nov 14 22:14:02 2023")
+        );
+        assert_eq!(finished.elapsed, Some(41.86));
+        assert!(finished
+            .summary
+            .unwrap()
+            .ends_with("scanned in 41.86 seconds"));
+        assert_eq!(finished.exit, Some(Exit::Success));
+        assert_eq!(finished.errormsg, None);
+        assert_eq!(
+            runstats.hosts,
+            Some(Hosts {
+                up: 1,
+                down: 3,
+                total: 4
+            })
+        );
+    }
+
+    #[test]
+    fn finished_with_error() {
+        let xml = report(
+            r#"<finished time="1700000001" exit="error" errormsg="Failed to resolve &quot;nohost&quot;."/>"#,
+        );
+        let finished = NmapRun::parse(&xml)
+            .unwrap()
+            .runstats
+            .unwrap()
+            .finished
+            .unwrap();
+        assert_eq!(finished.exit, Some(Exit::Error));
+        assert_eq!(
+            finished.errormsg.as_deref(),
+            Some("Failed to resolve \"nohost\".")
+        );
+        assert_eq!((finished.timestr, finished.elapsed), (None, None));
+
+        let xml = report(r#"<finished time="1" exit="aborted"/>"#);
+        assert!(NmapRun::parse(&xml).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use error_stack::ResultExt;
+use std::net::IpAddr;
+
+use crate::{
+    address::Address,
+    host::Host,
+    port::{Port, PortProtocol, PortState, PortStatus},
+    status::{HostState, Status},
+    Error, NmapRun, Result,
+};
+
+impl NmapRun {
+    /// Parses rustscan greppable output (`-g`), lines like `10.0.0.1 -> [22,80]`.
+    ///
+    /// Rustscan reports only open TCP ports. Its XML output is written by the
+    /// nmap it runs afterwards and is read by [`NmapRun::parse`].
+    pub fn parse_rustscan(text: &str) -> Result<Self> {
+        let mut nmaprun = NmapRun::new("rustscan");
+        let mut hosts = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let Some((addr, ports)) = line.split_once(" -> ") else {
+                continue;
+            };
+
+            let host = parse_line(addr.trim(), ports.trim())
+                .attach_printable_lazy(|| format!("line: {}", index + 1))?;
+            hosts.push(host);
+        }
+
+        nmaprun.hosts = Some(hosts).filter(|v| !v.is_empty());
+
+        Ok(nmaprun)
+    }
+}
+
+fn parse_line(addr: &str, ports: &str) -> Result<Host> {
+    let addr = addr
+        .parse::<IpAddr>()
+        .change_context(Error::FailedToParseRustscan)
+        .attach_printable_lazy(|| format!("address: \"{addr}\""))?;
+
+    let ports = ports
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .filter(|port| !port.trim().is_empty())
+        .map(|port| {
+            let port_number = port
+                .trim()
+                .parse::<u16>()
+                .change_context(Error::FailedToParseRustscan)
+                .attach_printable_lazy(|| format!("port: \"{port}\""))?;
+
+            Ok(Port {
+                protocol: PortProtocol::Tcp,
+                port_number,
+                status: PortStatus {
+                    state: PortState::Open,
+                    reason: None,
+                    reason_ttl: None,
+                },
+                service: None,
+                scripts: None,
+                sources: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let status = Status {
+        state: HostState::Up,
+        reason: None,
+        reason_ttl: None,
+    };
+    let mut host = Host::new(status, vec![Address::IpAddr(addr)]);
+    host.ports = Some(ports).filter(|v| !v.is_empty());
+
+    Ok(host)
+}
@@ -2,7 +2,7 @@ use error_stack::ResultExt;
 use roxmltree::Node;
 use serde::{Deserialize, Serialize};
 
-use crate::{port::PortProtocol, Attribute, Error, Result};
+use crate::{context::Context, port::PortProtocol, Attribute, Error, Result};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "snake_case")]
@@ -15,7 +15,7 @@ pub struct ScanInfo {
 }
 
 impl ScanInfo {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let ttype = node
             .attribute("type")
             .ok_or(Error::MissedAttribute)
@@ -26,8 +26,7 @@ impl ScanInfo {
             .attribute("protocol")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("protocol", "scaninfo")))
-            .map(str::parse::<PortProtocol>)?
-            .change_context(Error::FailedToParseAttribute)
+            .and_then(|value| ctx.variant::<PortProtocol>(node, "protocol", value))
             .attach_printable(Attribute(("protocol", "scaninfo")))?;
 
         let numservices = node
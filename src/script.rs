@@ -1,13 +1,17 @@
 use error_stack::ResultExt;
 use roxmltree::Node;
 use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::collections::BTreeMap;
 
-use crate::{Attribute, Error, Result};
+use crate::{context::Context, Attribute, Error, Result};
 
+#[skip_serializing_none]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Script {
     pub id: String,
     pub output: String,
+    pub data: Option<ScriptValue>,
 }
 
 impl Script {
@@ -24,6 +28,153 @@ impl Script {
             .attach_printable(Attribute(("output", "script")))?
             .to_string();
 
-        Ok(Script { id, output })
+        let data = ScriptValue::parse_children(node);
+
+        Ok(Script { id, output, data })
+    }
+}
+
+/// Collects `<script>` children of a `<hostscript>`, `<prescript>` or `<postscript>` node.
+pub(crate) fn parse_scripts_node(node: Node, ctx: &mut Context) -> Result<Option<Vec<Script>>> {
+    let mut scripts = Vec::new();
+
+    for child in node.children() {
+        if child.tag_name().name() == "script" {
+            scripts.extend(ctx.leaf(child, Script::parse)?);
+        }
+    }
+
+    Ok(Some(scripts).filter(|v| !v.is_empty()))
+}
+
+/// Structured NSE output built from nested `<table>` and `<elem>` elements.
+///
+/// Tables whose children have no `key` become lists, keyed tables become maps,
+/// and tables with both become a [`MixedTable`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScriptValue {
+    Scalar(String),
+    List(Vec<ScriptValue>),
+    Mixed(MixedTable),
+    Map(BTreeMap<String, ScriptValue>),
+}
+
+/// A table with keyed and unkeyed children, unkeyed ones keep their order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MixedTable {
+    pub keyed: BTreeMap<String, ScriptValue>,
+    pub unkeyed: Vec<ScriptValue>,
+}
+
+impl ScriptValue {
+    /// Parses a single `<table>` or `<elem>` node.
+    #[must_use]
+    pub fn parse(node: Node) -> Self {
+        if node.tag_name().name() == "table" {
+            ScriptValue::parse_children(node).unwrap_or(ScriptValue::List(Vec::new()))
+        } else {
+            ScriptValue::Scalar(node.text().unwrap_or_default().to_string())
+        }
+    }
+
+    /// Collects `<table>` and `<elem>` children of a `<script>` or `<table>` node.
+    /// Returns `None` if the node has no structured children.
+    #[must_use]
+    pub fn parse_children(node: Node) -> Option<Self> {
+        let children = node
+            .children()
+            .filter(|child| matches!(child.tag_name().name(), "table" | "elem"))
+            .map(|child| (child.attribute("key"), ScriptValue::parse(child)))
+            .collect::<Vec<_>>();
+
+        if children.is_empty() {
+            return None;
+        }
+
+        let mut keyed = BTreeMap::new();
+        let mut unkeyed = Vec::new();
+        for (key, value) in children {
+            match key {
+                Some(key) => {
+                    keyed.insert(key.to_string(), value);
+                }
+                None => unkeyed.push(value),
+            }
+        }
+
+        Some(if keyed.is_empty() {
+            ScriptValue::List(unkeyed)
+        } else if unkeyed.is_empty() {
+            ScriptValue::Map(keyed)
+        } else {
+            ScriptValue::Mixed(MixedTable { keyed, unkeyed })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MixedTable, Script, ScriptValue};
+    use roxmltree::Document;
+
+    #[test]
+    fn parse_nested_tables() {
+        let xml = r#"<script id="ssl-cert" output="...">
+            <table key="subject"><elem key="commonName">localhost</elem></table>
+            <table key="validity"><elem key="notAfter">2018-04-07T08:22:08</elem></table>
+            <table key="extensions"><table><elem key="name">a</elem></table><table><elem key="name">b</elem></table></table>
+            <elem key="pem"></elem>
+        </script>"#;
+
+        let doc = Document::parse(xml).unwrap();
+        let script = Script::parse(doc.root_element()).unwrap();
+
+        let Some(ScriptValue::Map(data)) = script.data else {
+            panic!("expected map, got {:?}", script.data);
+        };
+        let Some(ScriptValue::Map(validity)) = data.get("validity") else {
+            panic!("expected validity table");
+        };
+        assert_eq!(
+            validity.get("notAfter"),
+            Some(&ScriptValue::Scalar("2018-04-07T08:22:08".into()))
+        );
+        assert!(matches!(data.get("extensions"), Some(ScriptValue::List(v)) if v.len() == 2));
+        assert_eq!(data.get("pem"), Some(&ScriptValue::Scalar(String::new())));
+    }
+
+    #[test]
+    fn parse_mixed_table() {
+        let xml = r#"<script id="x" output="...">
+            <elem key="1">keyed</elem>
+            <elem>1</elem><elem>2</elem><elem>3</elem><elem>4</elem><elem>5</elem>
+            <elem>6</elem><elem>7</elem><elem>8</elem><elem>9</elem><elem>10</elem>
+            <elem key="name">a</elem>
+        </script>"#;
+
+        let doc = Document::parse(xml).unwrap();
+        let script = Script::parse(doc.root_element()).unwrap();
+
+        let Some(ScriptValue::Mixed(MixedTable { keyed, unkeyed })) = &script.data else {
+            panic!("expected mixed table, got {:?}", script.data);
+        };
+        assert_eq!(keyed.len(), 2);
+        assert_eq!(keyed["1"], ScriptValue::Scalar("keyed".into()));
+        let unkeyed = unkeyed
+            .iter()
+            .map(|value| match value {
+                ScriptValue::Scalar(text) => text.as_str(),
+                _ => panic!("expected scalar, got {value:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(unkeyed, ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]);
+
+        let json = serde_json::to_string(&script.data).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Option<ScriptValue>>(&json).unwrap(),
+            script.data
+        );
     }
 }
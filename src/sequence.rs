@@ -0,0 +1,135 @@
+use error_stack::ResultExt;
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::{Attribute, Error, Result};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TcpSequence {
+    pub index: u32,
+    pub difficulty: String,
+    pub values: String,
+}
+
+impl TcpSequence {
+    pub fn parse(node: Node) -> Result<Self> {
+        let index = node
+            .attribute("index")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("index", "tcpsequence")))
+            .map(str::parse::<u32>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("index", "tcpsequence")))?;
+
+        let difficulty = node
+            .attribute("difficulty")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("difficulty", "tcpsequence")))?
+            .to_string();
+
+        let values = node
+            .attribute("values")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("values", "tcpsequence")))?
+            .to_string();
+
+        Ok(TcpSequence {
+            index,
+            difficulty,
+            values,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IpIdSequence {
+    pub class: String,
+    pub values: String,
+}
+
+impl IpIdSequence {
+    pub fn parse(node: Node) -> Result<Self> {
+        let class = node
+            .attribute("class")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("class", "ipidsequence")))?
+            .to_string();
+
+        let values = node
+            .attribute("values")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("values", "ipidsequence")))?
+            .to_string();
+
+        Ok(IpIdSequence { class, values })
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TcpTsSequence {
+    pub class: String,
+    pub values: Option<String>,
+}
+
+impl TcpTsSequence {
+    pub fn parse(node: Node) -> Result<Self> {
+        let class = node
+            .attribute("class")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("class", "tcptssequence")))?
+            .to_string();
+
+        let values = node.attribute("values").map(Into::into);
+
+        Ok(TcpTsSequence { class, values })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fixture, NmapRun};
+
+    const HOSTS: &str = r#"<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/>
+<uptime seconds="86400" lastboot="Mon Oct  9 12:00:00 2023"/>
+<tcpsequence index="260" difficulty="Good luck!" values="8A0CE2B4,7F5E31A0"/>
+<ipidsequence class="All zeros" values="0,0,0"/>
+<tcptssequence class="1000HZ" values="1D4F7E2A,1D4F7E8E"/>
+<times srtt="512" rttvar="140" to="100000"/>
+</host>
+<host><status state="up"/><address addr="10.0.0.2" addrtype="ipv4"/>
+<uptime seconds=""/>
+<tcpsequence index="" difficulty="" values=""/>
+<tcptssequence class="none returned (unsupported)"/>
+<times srtt="-1" rttvar="-1" to="1000000"/>
+</host>"#;
+
+    #[test]
+    fn host_timing() {
+        let report = NmapRun::parse(&fixture::run(HOSTS)).unwrap();
+        let hosts = report.hosts.unwrap();
+
+        let uptime = hosts[0].uptime.as_ref().unwrap();
+        assert_eq!(uptime.seconds, 86400);
+        assert_eq!(uptime.lastboot.as_deref(), Some("Mon Oct  9 12:00:00 2023"));
+
+        let tcp = hosts[0].tcp_sequence.as_ref().unwrap();
+        assert_eq!(tcp.index, 260);
+        assert_eq!(tcp.difficulty, "Good luck!");
+        assert_eq!(tcp.values, "8A0CE2B4,7F5E31A0");
+        assert_eq!(hosts[0].ip_id_sequence.as_ref().unwrap().class, "All zeros");
+        let ts = hosts[0].tcp_ts_sequence.as_ref().unwrap();
+        assert_eq!(ts.values.as_deref(), Some("1D4F7E2A,1D4F7E8E"));
+
+        let times = hosts[0].times.as_ref().unwrap();
+        assert_eq!((times.srtt, times.rttvar, times.to), (512, 140, 100_000));
+
+        // Empty placeholders are skipped, `srtt="-1"` is kept as written
+        assert!(hosts[1].uptime.is_none());
+        assert!(hosts[1].tcp_sequence.is_none());
+        assert!(hosts[1].ip_id_sequence.is_none());
+        assert_eq!(hosts[1].tcp_ts_sequence.as_ref().unwrap().values, None);
+        assert_eq!(hosts[1].times.as_ref().unwrap().srtt, -1);
+    }
+}
@@ -2,9 +2,13 @@ use error_stack::ResultExt;
 use roxmltree::Node;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
+use std::fmt;
 use strum_macros::EnumString;
 
-use crate::{Attribute, Error, Result};
+use crate::{
+    context::{Context, OtherVariant},
+    Attribute, Error, Result,
+};
 
 #[derive(EnumString, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[strum(serialize_all = "lowercase")]
@@ -14,6 +18,27 @@ pub enum HostState {
     Down,
     Unknown,
     Skipped,
+    #[strum(disabled)]
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl OtherVariant for HostState {
+    fn other(value: String) -> Self {
+        HostState::Other(value)
+    }
+}
+
+impl fmt::Display for HostState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            HostState::Up => "up",
+            HostState::Down => "down",
+            HostState::Unknown => "unknown",
+            HostState::Skipped => "skipped",
+            HostState::Other(value) => value,
+        })
+    }
 }
 
 #[skip_serializing_none]
@@ -25,13 +50,12 @@ pub struct Status {
 }
 
 impl Status {
-    pub fn parse(node: Node) -> Result<Self> {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
         let state = node
             .attribute("state")
             .ok_or(Error::MissedAttribute)
             .attach_printable(Attribute(("state", "status")))
-            .map(str::parse::<HostState>)?
-            .change_context(Error::FailedToParseAttribute)
+            .and_then(|value| ctx.variant::<HostState>(node, "state", value))
             .attach_printable(Attribute(("state", "status")))?;
 
         let reason = node.attribute("reason").map(str::to_string);
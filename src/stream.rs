@@ -0,0 +1,493 @@
+use error_stack::{Report, ResultExt};
+use quick_xml::{events::Event as XmlEvent, Reader, Writer};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Read},
+};
+
+use crate::{
+    context::Context,
+    event::Event,
+    host::Host,
+    masscan,
+    runstats::RunStats,
+    scaninfo::ScanInfo,
+    script::{parse_scripts_node, Script},
+    warning::Location,
+    Attribute, Element, Error, NmapRun, Result,
+};
+
+/// Run-level data that nmap writes before the first `<host>`.
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Header {
+    pub scanner: String,
+    pub args: String,
+    pub start: u32,
+    pub version: String,
+    pub xmloutputversion: String,
+    pub scaninfos: Option<Vec<ScanInfo>>,
+    pub prescripts: Option<Vec<Script>>,
+}
+
+impl Header {
+    /// Parses the attributes of the `<nmaprun>` element.
+    ///
+    /// `scaninfos` and `prescripts` are left empty, they are children of the root.
+    pub fn parse(node: Node) -> Result<Self> {
+        if node.tag_name().name() != "nmaprun" {
+            return Err(Error::MissedElement).attach_printable(Element(("nmaprun", "root")));
+        }
+
+        let start = node
+            .attribute("start")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("start", "nmaprun")))
+            .map(str::parse::<u32>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("start", "nmaprun")))?;
+
+        let scanner = node
+            .attribute("scanner")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("scanner", "nmaprun")))?
+            .to_string();
+
+        // Masscan writes a dialect of nmap XML, see `crate::masscan`
+        if scanner != "nmap" && scanner != "masscan" {
+            return Err(error_stack::Report::new(Error::InvalidScannerType))
+                .attach_printable(format!("scanner: \"{scanner}\""));
+        }
+
+        // Masscan doesn't write the command line
+        let args = match node.attribute("args") {
+            None if scanner == "masscan" => String::new(),
+            args => args
+                .ok_or(Error::MissedAttribute)
+                .attach_printable(Attribute(("args", "nmaprun")))?
+                .to_string(),
+        };
+
+        let version = node
+            .attribute("version")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("version", "nmaprun")))?
+            .to_string();
+
+        let xmloutputversion = node
+            .attribute("xmloutputversion")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("xmloutputversion", "nmaprun")))?
+            .to_string();
+
+        Ok(Header {
+            scanner,
+            args,
+            start,
+            version,
+            xmloutputversion,
+            scaninfos: None,
+            prescripts: None,
+        })
+    }
+}
+
+/// Iterator over the hosts of a report, created by [`NmapRun::stream`].
+///
+/// Only one `<host>` element is kept in memory at a time. Run-level elements
+/// found between and after hosts (task events, `<postscript>`, `<runstats>`)
+/// are collected on the way and available through [`HostStream::into_run`].
+pub struct HostStream<R: BufRead> {
+    reader: Reader<Position<R>>,
+    buf: Vec<u8>,
+    header: Header,
+    pending: Option<Result<String>>,
+    postscripts: Option<Vec<Script>>,
+    events: Vec<Event>,
+    runstats: Option<RunStats>,
+    element: Option<(String, u64)>,
+    /// Top-level elements read so far by name, to index their locations.
+    elements: HashMap<String, usize>,
+    ctx: Context,
+    done: bool,
+}
+
+impl NmapRun {
+    /// Reads the report header and returns an iterator that parses hosts one by one.
+    ///
+    /// A report without closing `</nmaprun>` ends the iteration without an error,
+    /// a report cut inside an element yields an error. Masscan reports fail with
+    /// [`Error::UnsupportedStream`], masscan writes a `<host>` per port and
+    /// banner that can be merged only once the whole report is read.
+    pub fn stream<R: BufRead>(reader: R) -> Result<HostStream<R>> {
+        let stream = NmapRun::stream_records(reader)?;
+        if stream.is_masscan() {
+            return Err(Report::new(Error::UnsupportedStream))
+                .attach_printable(format!("scanner: \"{}\"", stream.header.scanner));
+        }
+
+        Ok(stream)
+    }
+
+    /// Same as [`NmapRun::stream`], masscan reports yield their host records
+    /// unmerged.
+    pub(crate) fn stream_records<R: BufRead>(reader: R) -> Result<HostStream<R>> {
+        let mut reader = Reader::from_reader(Position::new(reader));
+        let mut buf = Vec::new();
+
+        let header = loop {
+            buf.clear();
+            match reader
+                .read_event_into(&mut buf)
+                .change_context(Error::FailedToParseXml)
+                .map_err(|e| syntax_error(&reader, "/", e))?
+            {
+                XmlEvent::Start(start) => {
+                    let mut writer = Writer::new(Vec::new());
+                    writer
+                        .write_event(XmlEvent::Empty(start))
+                        .change_context(Error::FailedToParseXml)?;
+                    let xml = into_string(writer.into_inner())?;
+                    let (line, column) = reader.get_ref().markup();
+                    let root = Location {
+                        path: "/nmaprun".to_string(),
+                        line,
+                        column,
+                    };
+                    let mut ctx = Context::default();
+                    ctx.set_origin(Some(root.clone()));
+                    let doc = Document::parse(&xml).map_err(|e| ctx.syntax_error(e))?;
+                    break Header::parse(doc.root_element()).attach_printable(root)?;
+                }
+                XmlEvent::Empty(_) | XmlEvent::Eof => {
+                    return Err(Error::MissedElement)
+                        .attach_printable(Element(("nmaprun", "root")));
+                }
+                _ => {}
+            }
+        };
+
+        let mut stream = HostStream {
+            reader,
+            buf,
+            header,
+            pending: None,
+            postscripts: None,
+            events: Vec::new(),
+            runstats: None,
+            element: None,
+            elements: HashMap::new(),
+            ctx: Context::default(),
+            done: false,
+        };
+
+        // Everything up to the first host belongs to the header,
+        // errors are reported by the iterator to keep hosts recoverable
+        stream.pending = stream.next_host_xml().transpose();
+
+        Ok(stream)
+    }
+}
+
+impl<R: BufRead> HostStream<R> {
+    #[must_use]
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Run-level data collected so far, without hosts.
+    ///
+    /// Call it after the iterator is exhausted to get `<runstats>` and `<postscript>`.
+    #[must_use]
+    pub fn into_run(self) -> NmapRun {
+        let Header {
+            scanner,
+            args,
+            start,
+            version,
+            xmloutputversion,
+            scaninfos,
+            prescripts,
+        } = self.header;
+
+        NmapRun {
+            scanner,
+            args,
+            start,
+            version,
+            xmloutputversion,
+            scaninfos,
+            prescripts,
+            postscripts: self.postscripts,
+            events: Some(self.events).filter(|v| !v.is_empty()),
+            runstats: self.runstats,
+            hosts: None,
+            sources: None,
+        }
+    }
+
+    /// Name and byte offset of the last top-level element that was read.
+    pub(crate) fn last_element(&self) -> Option<(&str, u64)> {
+        self.element
+            .as_ref()
+            .map(|(name, offset)| (name.as_str(), *offset))
+    }
+
+    /// Masscan hosts are read by [`masscan::parse_host`], one record at a time.
+    fn is_masscan(&self) -> bool {
+        self.header.scanner == "masscan"
+    }
+
+    /// `true` once the end of the report is reached or the XML is broken.
+    pub(crate) fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Reads top-level elements until the next `<host>` and returns its XML.
+    fn next_host_xml(&mut self) -> Result<Option<String>> {
+        while !self.done {
+            self.buf.clear();
+            let offset = self.reader.buffer_position();
+            let event = self
+                .reader
+                .read_event_into(&mut self.buf)
+                .change_context(Error::FailedToParseXml)
+                .map_err(|e| syntax_error(&self.reader, "/nmaprun", e));
+
+            let (name, xml) = match event {
+                Ok(XmlEvent::Start(start)) => {
+                    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+                    let mut writer = Writer::new(Vec::new());
+                    writer
+                        .write_event(XmlEvent::Start(start))
+                        .change_context(Error::FailedToParseXml)?;
+                    let origin = self.enter(&name, offset);
+                    let xml = read_to_end(&mut self.reader, writer)
+                        .map_err(|e| syntax_error(&self.reader, &origin.path, e))?;
+                    (name, xml)
+                }
+                Ok(XmlEvent::Empty(start)) => {
+                    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+                    let mut writer = Writer::new(Vec::new());
+                    writer
+                        .write_event(XmlEvent::Empty(start))
+                        .change_context(Error::FailedToParseXml)?;
+                    self.enter(&name, offset);
+                    (name, into_string(writer.into_inner())?)
+                }
+                Ok(XmlEvent::End(_) | XmlEvent::Eof) => {
+                    self.done = true;
+                    continue;
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    self.done = true;
+                    return Err(e);
+                }
+            };
+
+            if name == "host" {
+                return Ok(Some(xml));
+            }
+            self.collect(&xml)?;
+        }
+
+        Ok(None)
+    }
+
+    /// Remembers the top-level element that is being read and returns its location.
+    fn enter(&mut self, name: &str, offset: u64) -> Location {
+        self.element = Some((name.to_string(), offset));
+
+        let index = self.elements.entry(name.to_string()).or_default();
+        *index += 1;
+        let path = format!("/nmaprun/{name}[{index}]");
+
+        let (line, column) = self.reader.get_ref().markup();
+        let origin = Location { path, line, column };
+        self.ctx.set_origin(Some(origin.clone()));
+        origin
+    }
+
+    fn collect(&mut self, xml: &str) -> Result<()> {
+        let doc = Document::parse(xml).map_err(|e| self.ctx.syntax_error(e))?;
+        let node = doc.root_element();
+
+        self.parse_element(node)
+            .map_err(|e| self.ctx.located(e, node))
+    }
+
+    fn parse_element(&mut self, node: Node) -> Result<()> {
+        match node.tag_name().name() {
+            "scaninfo" if self.is_masscan() => self
+                .header
+                .scaninfos
+                .get_or_insert_with(Vec::new)
+                .push(masscan::parse_scaninfo(node, &mut self.ctx)?),
+            "scaninfo" => self
+                .header
+                .scaninfos
+                .get_or_insert_with(Vec::new)
+                .push(ScanInfo::parse(node, &mut self.ctx)?),
+            "prescript" => self.header.prescripts = parse_scripts_node(node, &mut self.ctx)?,
+            "postscript" => self.postscripts = parse_scripts_node(node, &mut self.ctx)?,
+            "runstats" => {
+                self.runstats = Some(RunStats::parse(node, &mut self.ctx)?);
+                // `<runstats>` is always the last element, there is no need
+                // to wait for `</nmaprun>` of a report that is still being written
+                self.done = true;
+            }
+            _ => self.events.extend(Event::parse(node)?),
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for HostStream<R> {
+    type Item = Result<Host>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let xml = match self.pending.take() {
+            Some(xml) => xml,
+            None => self.next_host_xml().transpose()?,
+        };
+        let xml = match xml {
+            Ok(xml) => xml,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let host = Document::parse(&xml)
+            .map_err(|e| self.ctx.syntax_error(e))
+            .and_then(|doc| {
+                let node = doc.root_element();
+                let host = if self.is_masscan() {
+                    masscan::parse_host(node, &mut self.ctx)
+                } else {
+                    Host::parse(node, &mut self.ctx)
+                };
+                host.map_err(|e| self.ctx.located(e, node))
+            })
+            .attach_printable_lazy(|| {
+                format!(
+                    "version: {}, xmloutputversion: {}",
+                    self.header.version, self.header.xmloutputversion
+                )
+            });
+
+        Some(host)
+    }
+}
+
+/// Attaches the start of the markup the reader failed on to a syntax error,
+/// `path` is the innermost element known to contain it.
+fn syntax_error<R: BufRead>(
+    reader: &Reader<Position<R>>,
+    path: &str,
+    report: Report<Error>,
+) -> Report<Error> {
+    let (line, column) = reader.get_ref().markup();
+    report.attach_printable(Location {
+        path: path.to_string(),
+        line,
+        column,
+    })
+}
+
+/// Copies events into `writer` until the element opened right before is closed.
+fn read_to_end<R: BufRead>(reader: &mut Reader<R>, mut writer: Writer<Vec<u8>>) -> Result<String> {
+    let mut buf = Vec::new();
+    let mut depth = 1u32;
+
+    while depth > 0 {
+        buf.clear();
+        let event = reader
+            .read_event_into(&mut buf)
+            .change_context(Error::FailedToParseXml)?;
+
+        match event {
+            XmlEvent::Start(_) => depth += 1,
+            XmlEvent::End(_) => depth -= 1,
+            XmlEvent::Eof => {
+                return Err(Error::FailedToParseXml)
+                    .attach_printable("unexpected end of file inside an element");
+            }
+            _ => {}
+        }
+
+        writer
+            .write_event(event)
+            .change_context(Error::FailedToParseXml)?;
+    }
+
+    into_string(writer.into_inner())
+}
+
+/// Reader that tracks the line and column of the last consumed `<`.
+///
+/// `quick-xml` may consume the `<` of an element together with the preceding
+/// text, so the position of the markup start is remembered while consuming.
+struct Position<R> {
+    inner: R,
+    line: u32,
+    column: u32,
+    markup: (u32, u32),
+}
+
+impl<R> Position<R> {
+    fn new(inner: R) -> Self {
+        Position {
+            inner,
+            line: 1,
+            column: 1,
+            markup: (1, 1),
+        }
+    }
+
+    /// Line and column of the start of the last read element.
+    fn markup(&self) -> (u32, u32) {
+        self.markup
+    }
+}
+
+impl<R: BufRead> Read for Position<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for Position<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The consumed bytes are still in the buffer, no I/O happens here
+        if let Ok(buf) = self.inner.fill_buf() {
+            for byte in buf.iter().take(amt) {
+                if *byte == b'<' {
+                    self.markup = (self.line, self.column);
+                }
+                if *byte == b'\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else if byte & 0xC0 != 0x80 {
+                    // Columns are counted in characters like `roxmltree` does
+                    self.column += 1;
+                }
+            }
+        }
+        self.inner.consume(amt);
+    }
+}
+
+fn into_string(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).change_context(Error::FailedToParseXml)
+}
@@ -0,0 +1,340 @@
+#[cfg(feature = "csv")]
+use error_stack::ResultExt;
+use std::fmt;
+#[cfg(feature = "csv")]
+use std::io::Write;
+use strum_macros::EnumString;
+
+#[cfg(feature = "csv")]
+use crate::{address::Address, host::Host, port::Port, status::HostState, Error, NmapRun, Result};
+
+/// A column of the flattened table, see [`TableWriter`].
+#[derive(EnumString, Clone, Copy, Debug, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Column {
+    /// First IP address of the host
+    Ip,
+    /// First MAC address of the host
+    Mac,
+    /// Hostnames separated by spaces
+    Hostnames,
+    Protocol,
+    Port,
+    State,
+    Reason,
+    Service,
+    Product,
+    Version,
+    ExtraInfo,
+    Confidence,
+    /// Name of the best OS match
+    Os,
+}
+
+impl Column {
+    pub const ALL: [Column; 13] = [
+        Column::Ip,
+        Column::Mac,
+        Column::Hostnames,
+        Column::Protocol,
+        Column::Port,
+        Column::State,
+        Column::Reason,
+        Column::Service,
+        Column::Product,
+        Column::Version,
+        Column::ExtraInfo,
+        Column::Confidence,
+        Column::Os,
+    ];
+
+    #[cfg(feature = "csv")]
+    fn value(self, host: &Host, port: Option<&Port>) -> String {
+        let service = port.and_then(|port| port.service.as_ref());
+
+        let value = match self {
+            Column::Ip => host.ip().map(|ip| ip.to_string()),
+            Column::Mac => host.addresses.iter().find_map(|address| match address {
+                Address::MacAddr { addr, .. } => Some(addr.to_string()),
+                Address::IpAddr(_) => None,
+            }),
+            Column::Hostnames => host.host_names.as_ref().map(|names| {
+                names
+                    .iter()
+                    .map(|name| name.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }),
+            Column::Protocol => port.map(|port| port.protocol.to_string()),
+            Column::Port => port.map(|port| port.port_number.to_string()),
+            Column::State => port.map(|port| port.status.state.to_string()),
+            Column::Reason => port.and_then(|port| port.status.reason.clone()),
+            Column::Service => service.map(|service| service.name.clone()),
+            Column::Product => service.and_then(|service| service.product.clone()),
+            Column::Version => service.and_then(|service| service.version.clone()),
+            Column::ExtraInfo => service.and_then(|service| service.extra_info.clone()),
+            Column::Confidence => service
+                .and_then(|service| service.confidence_level)
+                .map(|conf| conf.to_string()),
+            Column::Os => host
+                .os
+                .as_ref()
+                .and_then(|os| os.best_match())
+                .map(|osmatch| osmatch.name.clone()),
+        };
+
+        value.unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Column::Ip => "ip",
+            Column::Mac => "mac",
+            Column::Hostnames => "hostnames",
+            Column::Protocol => "protocol",
+            Column::Port => "port",
+            Column::State => "state",
+            Column::Reason => "reason",
+            Column::Service => "service",
+            Column::Product => "product",
+            Column::Version => "version",
+            Column::ExtraInfo => "extrainfo",
+            Column::Confidence => "confidence",
+            Column::Os => "os",
+        })
+    }
+}
+
+/// Options of [`TableWriter`].
+#[derive(Clone, Debug)]
+pub struct TableOptions {
+    /// `b','` for CSV (default), `b'\t'` for TSV.
+    pub delimiter: u8,
+    /// Columns in the order they are written, all columns by default.
+    pub columns: Vec<Column>,
+    /// Write a single row without port values for up hosts without open ports.
+    pub hosts_without_ports: bool,
+    /// Prefix values that spreadsheets would run as formulas with `'`, see
+    /// [`escape_formula`] (enabled by default).
+    pub escape_formulas: bool,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        TableOptions {
+            delimiter: b',',
+            columns: Column::ALL.to_vec(),
+            hosts_without_ports: false,
+            escape_formulas: true,
+        }
+    }
+}
+
+/// Writes hosts as CSV or TSV, one row per open port of a host.
+///
+/// Ports that are `open` or `open|filtered` make rows, the header is written
+/// before the first row or on flush, so a table without rows still has it.
+/// Values are quoted when they contain the delimiter, quotes or line breaks.
+#[cfg(feature = "csv")]
+pub struct TableWriter<W: Write> {
+    writer: csv::Writer<W>,
+    options: TableOptions,
+    header: bool,
+}
+
+#[cfg(feature = "csv")]
+impl<W: Write> TableWriter<W> {
+    #[must_use]
+    pub fn new(writer: W, options: TableOptions) -> Self {
+        let writer = csv::WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .from_writer(writer);
+
+        TableWriter {
+            writer,
+            options,
+            header: false,
+        }
+    }
+
+    pub fn write_host(&mut self, host: &Host) -> Result<()> {
+        let ports = host
+            .ports
+            .iter()
+            .flatten()
+            .filter(|port| port.is_open())
+            .collect::<Vec<_>>();
+
+        if ports.is_empty() {
+            if self.options.hosts_without_ports && host.status.state == HostState::Up {
+                self.write_row(host, None)?;
+            }
+            return Ok(());
+        }
+
+        for port in ports {
+            self.write_row(host, Some(port))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.write_header()?;
+        self.writer
+            .flush()
+            .change_context(Error::FailedToWriteTable)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        if self.header {
+            return Ok(());
+        }
+        self.header = true;
+
+        let names = self.options.columns.iter().map(ToString::to_string);
+        self.writer
+            .write_record(names)
+            .change_context(Error::FailedToWriteTable)
+    }
+
+    fn write_row(&mut self, host: &Host, port: Option<&Port>) -> Result<()> {
+        self.write_header()?;
+
+        let values = self.options.columns.iter().map(|column| {
+            let value = column.value(host, port);
+            if self.options.escape_formulas {
+                escape_formula(value)
+            } else {
+                value
+            }
+        });
+
+        self.writer
+            .write_record(values)
+            .change_context(Error::FailedToWriteTable)
+    }
+}
+
+/// Neutralises a value that a spreadsheet would run as a formula.
+///
+/// Banners, titles and names in scan results come from the scanned hosts,
+/// a value starting with `=`, `+`, `-`, `@`, a tab or a carriage return is
+/// prefixed with `'` so that it is shown as text.
+#[must_use]
+pub fn escape_formula(value: String) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value
+    }
+}
+
+#[cfg(feature = "csv")]
+impl NmapRun {
+    /// Writes the hosts of the report as CSV or TSV, see [`TableWriter`].
+    pub fn write_table(&self, writer: impl Write, options: TableOptions) -> Result<()> {
+        let mut table = TableWriter::new(writer, options);
+        for host in self.hosts.iter().flatten() {
+            table.write_host(host)?;
+        }
+        table.flush()
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod test {
+    use crate::{
+        fixture,
+        table::{escape_formula, Column, TableOptions},
+        NmapRun,
+    };
+
+    const XML: &str = r#"<nmaprun scanner="nmap" args="nmap -sV 10.0.0.0/24" start="1" version="7.94" xmloutputversion="1.05">
+<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/><address addr="00:11:22:33:44:55" addrtype="mac"/>
+<hostnames><hostname name="gw.lan" type="PTR"/></hostnames>
+<ports>
+<port protocol="tcp" portid="22"><state state="open" reason="syn-ack"/><service name="ssh" product="OpenSSH" version="9.6" extrainfo="Ubuntu, protocol 2.0" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="23"><state state="closed" reason="reset"/></port>
+</ports>
+</host>
+<host><status state="up"/><address addr="10.0.0.2" addrtype="ipv4"/></host>
+<host><status state="down"/><address addr="10.0.0.3" addrtype="ipv4"/></host>
+</nmaprun>"#;
+
+    fn table(report: &NmapRun, options: TableOptions) -> String {
+        let mut out = Vec::new();
+        report.write_table(&mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn write_table() {
+        let report = NmapRun::parse(XML).unwrap();
+
+        assert_eq!(
+            table(&report, TableOptions::default()),
+            "ip,mac,hostnames,protocol,port,state,reason,service,product,version,extrainfo,confidence,os\n\
+             10.0.0.1,00:11:22:33:44:55,gw.lan,tcp,22,open,syn-ack,ssh,OpenSSH,9.6,\"Ubuntu, protocol 2.0\",10,\n"
+        );
+
+        let options = TableOptions {
+            delimiter: b'\t',
+            columns: vec![Column::Ip, Column::Port, Column::Service],
+            hosts_without_ports: true,
+            escape_formulas: true,
+        };
+        assert_eq!(
+            table(&report, options),
+            "ip\tport\tservice\n10.0.0.1\t22\tssh\n10.0.0.2\t\t\n"
+        );
+
+        assert_eq!("extrainfo".parse::<Column>(), Ok(Column::ExtraInfo));
+    }
+
+    #[test]
+    fn header_without_rows() {
+        let report = NmapRun::parse(&fixture::run("")).unwrap();
+        let options = TableOptions {
+            columns: vec![Column::Ip, Column::Port],
+            ..TableOptions::default()
+        };
+
+        assert_eq!(table(&report, options), "ip,port\n");
+    }
+
+    #[test]
+    fn escape_formulas() {
+        let xml = XML.replace("Ubuntu, protocol 2.0", "=HYPERLINK(&quot;http://x&quot;)");
+        let report = NmapRun::parse(&xml).unwrap();
+        let options = TableOptions {
+            columns: vec![Column::Port, Column::ExtraInfo],
+            ..TableOptions::default()
+        };
+
+        assert_eq!(
+            table(&report, options.clone()),
+            "port,extrainfo\n22,\"'=HYPERLINK(\"\"http://x\"\")\"\n"
+        );
+
+        let options = TableOptions {
+            escape_formulas: false,
+            ..options
+        };
+        assert_eq!(
+            table(&report, options),
+            "port,extrainfo\n22,\"=HYPERLINK(\"\"http://x\"\")\"\n"
+        );
+
+        for (value, escaped) in [
+            ("+1", "'+1"),
+            ("-x", "'-x"),
+            ("@SUM", "'@SUM"),
+            ("ssh", "ssh"),
+        ] {
+            assert_eq!(escape_formula(value.to_string()), escaped);
+        }
+    }
+}
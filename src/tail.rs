@@ -0,0 +1,136 @@
+use std::{
+    io::{BufReader, Read},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{stream::HostStream, NmapRun, Result};
+
+/// Reader over a file that is still being written, like `tail -f`.
+///
+/// Instead of reporting the end of file it waits for new data to appear.
+/// The end of file is reported only when `timeout` elapses without new data.
+pub struct Tail<R> {
+    inner: R,
+    interval: Duration,
+    timeout: Option<Duration>,
+}
+
+impl<R: Read> Tail<R> {
+    pub fn new(inner: R) -> Self {
+        Tail {
+            inner,
+            interval: Duration::from_millis(500),
+            timeout: None,
+        }
+    }
+
+    /// How often to check the file for new data (500ms by default).
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Give up waiting after `timeout` without new data (waits forever by default).
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl<R: Read> Read for Tail<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let started = Instant::now();
+
+        loop {
+            let read = self.inner.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            if self
+                .timeout
+                .is_some_and(|timeout| started.elapsed() >= timeout)
+            {
+                return Ok(0);
+            }
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+impl NmapRun {
+    /// Streams hosts from a report that nmap is still writing.
+    ///
+    /// Hosts are yielded as soon as their `<host>` element is closed, the
+    /// iteration finishes once `<runstats>` is written.
+    pub fn follow<R: Read>(reader: R) -> Result<HostStream<BufReader<Tail<R>>>> {
+        NmapRun::follow_with(Tail::new(reader))
+    }
+
+    /// Same as [`NmapRun::follow`] with a configured [`Tail`].
+    pub fn follow_with<R: Read>(tail: Tail<R>) -> Result<HostStream<BufReader<Tail<R>>>> {
+        NmapRun::stream(BufReader::new(tail))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Write, thread, time::Duration};
+
+    use crate::{tail::Tail, NmapRun};
+
+    const HEADER: &str = r#"<nmaprun scanner="nmap" args="nmap 10.0.0.0/30" start="1" version="7.94" xmloutputversion="1.05">
+"#;
+
+    fn host(ip: &str) -> String {
+        format!(r#"<host><status state="up"/><address addr="{ip}" addrtype="ipv4"/></host>"#)
+    }
+
+    fn tail(file: fs::File) -> Tail<fs::File> {
+        Tail::new(file)
+            .interval(Duration::from_millis(10))
+            .timeout(Duration::from_secs(5))
+    }
+
+    #[test]
+    fn follow_appended_hosts() {
+        let path = std::env::temp_dir().join(format!("n2j-follow-{}.xml", std::process::id()));
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(HEADER.as_bytes()).unwrap();
+        file.write_all(host("10.0.0.1").as_bytes()).unwrap();
+
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            file.write_all(host("10.0.0.2").as_bytes()).unwrap();
+            thread::sleep(Duration::from_millis(100));
+            file.write_all(b"<runstats><finished time=\"2\"/></runstats></nmaprun>")
+                .unwrap();
+        });
+
+        let hosts = NmapRun::follow_with(tail(fs::File::open(&path).unwrap()))
+            .unwrap()
+            .map(|host| host.unwrap().ip().unwrap().to_string())
+            .collect::<Vec<_>>();
+        writer.join().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(hosts, ["10.0.0.1", "10.0.0.2"]);
+    }
+
+    #[test]
+    fn idle_timeout() {
+        let path = std::env::temp_dir().join(format!("n2j-idle-{}.xml", std::process::id()));
+        fs::write(&path, format!("{HEADER}{}", host("10.0.0.1"))).unwrap();
+
+        // nmap never finishes the report, the stream ends after the timeout
+        let file = fs::File::open(&path).unwrap();
+        let tail = tail(file).timeout(Duration::from_millis(50));
+        let hosts = NmapRun::follow_with(tail).unwrap().collect::<Vec<_>>();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(hosts.len(), 1);
+        assert!(hosts[0].is_ok());
+    }
+}
@@ -0,0 +1,43 @@
+use error_stack::ResultExt;
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+
+use crate::{Attribute, Error, Result};
+
+/// Round-trip timing estimates of a host, in microseconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Times {
+    pub srtt: i64,
+    pub rttvar: i64,
+    pub to: i64,
+}
+
+impl Times {
+    pub fn parse(node: Node) -> Result<Self> {
+        let srtt = node
+            .attribute("srtt")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("srtt", "times")))
+            .map(str::parse::<i64>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("srtt", "times")))?;
+
+        let rttvar = node
+            .attribute("rttvar")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("rttvar", "times")))
+            .map(str::parse::<i64>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("rttvar", "times")))?;
+
+        let to = node
+            .attribute("to")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("to", "times")))
+            .map(str::parse::<i64>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("to", "times")))?;
+
+        Ok(Times { srtt, rttvar, to })
+    }
+}
@@ -0,0 +1,138 @@
+use error_stack::ResultExt;
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::net::IpAddr;
+
+use crate::{context::Context, Attribute, Error, Result};
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trace {
+    pub port: Option<u16>,
+    pub proto: Option<String>,
+    pub hops: Vec<Hop>,
+}
+
+impl Trace {
+    pub fn parse(node: Node, ctx: &mut Context) -> Result<Self> {
+        // Nmap writes `port=""` and `proto=""` when traceroute was not able to pick a probe
+        let port = attribute(node, "port")
+            .map(str::parse::<u16>)
+            .transpose()
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("port", "trace")))?;
+
+        let proto = attribute(node, "proto").map(Into::into);
+
+        let mut hops = Vec::new();
+
+        for child in node.children() {
+            #[allow(clippy::single_match)]
+            match child.tag_name().name() {
+                "hop" => hops.extend(ctx.leaf(child, Hop::parse)?),
+                _ => {}
+            }
+        }
+
+        Ok(Trace { port, proto, hops })
+    }
+
+    /// TTLs that have no `<hop>` element.
+    ///
+    /// Nmap omits hops that did not respond, so gaps in the TTL sequence
+    /// (up to the last recorded hop) are reported here.
+    #[must_use]
+    pub fn omitted_hops(&self) -> Vec<u32> {
+        let max = self.hops.iter().map(|hop| hop.ttl).max().unwrap_or(0);
+        (1..=max)
+            .filter(|ttl| !self.hops.iter().any(|hop| hop.ttl == *ttl))
+            .collect()
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Hop {
+    pub ttl: u32,
+    pub ipaddr: Option<IpAddr>,
+    pub rtt: Option<f64>,
+    pub host: Option<String>,
+}
+
+impl Hop {
+    pub fn parse(node: Node) -> Result<Self> {
+        let ttl = node
+            .attribute("ttl")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("ttl", "hop")))
+            .map(str::parse::<u32>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("ttl", "hop")))?;
+
+        let ipaddr = attribute(node, "ipaddr")
+            .map(str::parse::<IpAddr>)
+            .transpose()
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("ipaddr", "hop")))?;
+
+        // Hops shared with a previously traced host are written with `rtt="--"`
+        let rtt = attribute(node, "rtt")
+            .filter(|rtt| *rtt != "--")
+            .map(str::parse::<f64>)
+            .transpose()
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("rtt", "hop")))?;
+
+        let host = attribute(node, "host").map(Into::into);
+
+        Ok(Hop {
+            ttl,
+            ipaddr,
+            rtt,
+            host,
+        })
+    }
+}
+
+fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute(name).filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fixture, NmapRun};
+
+    const HOSTS: &str = r#"<host><status state="up"/><address addr="10.0.0.9" addrtype="ipv4"/>
+<trace port="80" proto="tcp">
+<hop ttl="1" ipaddr="10.0.0.1" rtt="0.51" host="gw.lan"/>
+<hop ttl="2" ipaddr="192.0.2.1" rtt="--"/>
+<hop ttl="5" ipaddr="10.0.0.9" rtt="4.20"/>
+</trace>
+</host>
+<host><status state="up"/><address addr="10.0.0.10" addrtype="ipv4"/>
+<trace port="" proto=""/>
+</host>"#;
+
+    #[test]
+    fn trace() {
+        let report = NmapRun::parse(&fixture::run(HOSTS)).unwrap();
+        let hosts = report.hosts.unwrap();
+
+        let trace = hosts[0].trace.as_ref().unwrap();
+        assert_eq!(trace.port, Some(80));
+        assert_eq!(trace.proto.as_deref(), Some("tcp"));
+        assert_eq!(trace.hops.len(), 3);
+        assert_eq!(trace.hops[0].ipaddr, Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(trace.hops[0].rtt, Some(0.51));
+        assert_eq!(trace.hops[0].host.as_deref(), Some("gw.lan"));
+        assert_eq!(trace.hops[1].rtt, None);
+        assert_eq!(trace.hops[1].host, None);
+        assert_eq!(trace.omitted_hops(), vec![3, 4]);
+
+        let empty = hosts[1].trace.as_ref().unwrap();
+        assert_eq!((empty.port, empty.proto.as_deref()), (None, None));
+        assert!(empty.hops.is_empty());
+        assert!(empty.omitted_hops().is_empty());
+    }
+}
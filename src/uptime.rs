@@ -0,0 +1,29 @@
+use error_stack::ResultExt;
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::{Attribute, Error, Result};
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Uptime {
+    pub seconds: u64,
+    pub lastboot: Option<String>,
+}
+
+impl Uptime {
+    pub fn parse(node: Node) -> Result<Self> {
+        let seconds = node
+            .attribute("seconds")
+            .ok_or(Error::MissedAttribute)
+            .attach_printable(Attribute(("seconds", "uptime")))
+            .map(str::parse::<u64>)?
+            .change_context(Error::FailedToParseAttribute)
+            .attach_printable(Attribute(("seconds", "uptime")))?;
+
+        let lastboot = node.attribute("lastboot").map(Into::into);
+
+        Ok(Uptime { seconds, lastboot })
+    }
+}
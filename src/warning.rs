@@ -0,0 +1,153 @@
+use error_stack::{FrameKind, Report};
+use roxmltree::Node;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::fmt;
+
+use crate::Error;
+
+/// A problem that did not stop parsing, but made some data to be dropped.
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Warning {
+    pub message: String,
+    /// Name of the dropped element.
+    pub element: Option<String>,
+    /// Byte offset of the dropped element in the report.
+    pub offset: Option<u64>,
+    /// Address of the dropped host, if it was written before the damage.
+    pub address: Option<String>,
+    pub location: Option<Location>,
+}
+
+impl Warning {
+    pub(crate) fn at(node: Node, location: Location, message: String) -> Self {
+        Warning {
+            message,
+            element: Some(node.tag_name().name().to_string()),
+            offset: u64::try_from(node.range().start).ok(),
+            address: None,
+            location: Some(location),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(location) = &self.location {
+            write!(f, " at {location}")?;
+        } else if let Some(element) = &self.element {
+            write!(f, " (element: \"{element}\"")?;
+            if let Some(offset) = self.offset {
+                write!(f, ", offset: {offset}")?;
+            }
+            if let Some(address) = &self.address {
+                write!(f, ", address: {address}")?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Position of an element in the report.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    /// Breadcrumb path like `/nmaprun/host[42]/ports/port[7]/service`,
+    /// indexes are 1-based. Children of `<nmaprun>` always have one, so that
+    /// a streamed report locates them as a whole one does, deeper elements
+    /// only when they are repeated.
+    pub path: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Location {
+    #[must_use]
+    pub fn of(node: Node) -> Self {
+        let mut path = Vec::new();
+
+        for element in node.ancestors().filter(Node::is_element) {
+            let name = element.tag_name().name();
+            let same_name =
+                |sibling: &Node| sibling.is_element() && sibling.tag_name().name() == name;
+
+            let index = element.prev_siblings().filter(same_name).count();
+            let repeated = index > 1 || element.next_siblings().skip(1).any(|n| same_name(&n));
+            let top_level = element.parent_element().is_some_and(|parent| {
+                parent == node.document().root_element() && parent.has_tag_name("nmaprun")
+            });
+
+            if repeated || top_level {
+                path.push(format!("{name}[{index}]"));
+            } else {
+                path.push(name.to_string());
+            }
+        }
+
+        path.reverse();
+
+        let pos = node.document().text_pos_at(node.range().start);
+
+        Location {
+            path: format!("/{}", path.join("/")),
+            line: pos.row,
+            column: pos.col,
+        }
+    }
+
+    /// Moves a location inside a fragment to the report the fragment was cut
+    /// from, `origin` is the location of the fragment's root element.
+    #[must_use]
+    pub fn within(self, origin: &Location) -> Self {
+        let inner = self
+            .path
+            .trim_start_matches('/')
+            .split_once('/')
+            .map_or("", |(_, inner)| inner);
+
+        Location {
+            path: if inner.is_empty() {
+                origin.path.clone()
+            } else {
+                format!("{}/{inner}", origin.path)
+            },
+            line: origin.line + self.line - 1,
+            column: if self.line == 1 {
+                origin.column + self.column - 1
+            } else {
+                self.column
+            },
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (line {}, column {})",
+            self.path, self.line, self.column
+        )
+    }
+}
+
+/// Error message with its root cause, e.g.
+/// "failed to parse XML document: tag not closed: `>` not found before end of input".
+pub(crate) fn describe(report: &Report<Error>) -> String {
+    let cause = report
+        .frames()
+        .filter_map(|frame| match frame.kind() {
+            FrameKind::Context(context) => Some(context.to_string()),
+            FrameKind::Attachment(_) => None,
+        })
+        .last();
+
+    match cause {
+        Some(cause) if cause != report.current_context().to_string() => {
+            format!("{}: {cause}", report.current_context())
+        }
+        _ => report.current_context().to_string(),
+    }
+}
@@ -0,0 +1,620 @@
+use std::fmt::Display;
+
+use crate::{
+    address::Address,
+    event::{Event, Level, Task, TaskProgress},
+    host::Host,
+    hostname::Hostname,
+    os::{Cpe, Os, OsClass, OsMatch, PortUsed},
+    port::{ExtraPorts, ExtraReasons, Port, PortStatus, Service},
+    runstats::{Finished, Hosts, RunStats},
+    scaninfo::ScanInfo,
+    script::{MixedTable, Script, ScriptValue},
+    trace::{Hop, Trace},
+    NmapRun,
+};
+
+impl NmapRun {
+    /// Writes the report back to nmap XML.
+    ///
+    /// Elements are written in the order of `nmap.dtd`, so a report parsed from
+    /// a valid document gives a valid document. Data that the model doesn't keep
+    /// (e.g. `startstr`, `<hosthint>`, comments) is not restored, and elements
+    /// missing in the model (e.g. `<runstats>` of an interrupted scan) are not
+    /// made up.
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut xml = Xml::default();
+
+        xml.out
+            .push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.out.push_str("<!DOCTYPE nmaprun>\n");
+
+        let tag = Tag::new("nmaprun")
+            .attr("scanner", &self.scanner)
+            .attr("args", &self.args)
+            .attr("start", self.start)
+            .attr("version", &self.version)
+            .attr("xmloutputversion", &self.xmloutputversion);
+
+        xml.open(&tag);
+        xml.all(self.scaninfos.iter().flatten());
+        xml.all(self.events.iter().flatten());
+        if let Some(scripts) = &self.prescripts {
+            xml.scripts("prescript", scripts);
+        }
+        xml.all(self.hosts.iter().flatten());
+        if let Some(scripts) = &self.postscripts {
+            xml.scripts("postscript", scripts);
+        }
+        xml.all(&self.runstats);
+        xml.close("nmaprun");
+
+        xml.out
+    }
+}
+
+/// Model types that are written as an XML element.
+trait ToXml {
+    fn write(&self, xml: &mut Xml);
+}
+
+#[derive(Default)]
+struct Xml {
+    out: String,
+}
+
+impl Xml {
+    fn open(&mut self, tag: &Tag) {
+        self.start_tag(tag);
+        self.out.push_str(">\n");
+    }
+
+    fn empty(&mut self, tag: &Tag) {
+        self.start_tag(tag);
+        self.out.push_str("/>\n");
+    }
+
+    fn close(&mut self, name: &str) {
+        self.out.push_str("</");
+        self.out.push_str(name);
+        self.out.push_str(">\n");
+    }
+
+    fn text(&mut self, tag: &Tag, text: &str) {
+        if text.is_empty() {
+            return self.empty(tag);
+        }
+        self.start_tag(tag);
+        self.out.push('>');
+        self.out.push_str(&escape(text, false));
+        self.close(tag.name);
+    }
+
+    fn start_tag(&mut self, tag: &Tag) {
+        self.out.push('<');
+        self.out.push_str(tag.name);
+        self.out.push_str(&tag.attrs);
+    }
+
+    fn all<'a, T: ToXml + 'a>(&mut self, items: impl IntoIterator<Item = &'a T>) {
+        for item in items {
+            item.write(self);
+        }
+    }
+
+    /// Writes `tag` as an empty element, or wraps the children if there are any.
+    fn parent<'a, T: ToXml + 'a>(&mut self, tag: &Tag, children: impl IntoIterator<Item = &'a T>) {
+        let mut children = children.into_iter().peekable();
+        if children.peek().is_none() {
+            return self.empty(tag);
+        }
+        self.open(tag);
+        self.all(children);
+        self.close(tag.name);
+    }
+
+    fn scripts(&mut self, name: &'static str, scripts: &[Script]) {
+        self.parent(&Tag::new(name), scripts);
+    }
+}
+
+/// Start tag with its escaped attributes.
+struct Tag {
+    name: &'static str,
+    attrs: String,
+}
+
+impl Tag {
+    fn new(name: &'static str) -> Self {
+        Tag {
+            name,
+            attrs: String::new(),
+        }
+    }
+
+    fn attr(mut self, name: &str, value: impl Display) -> Self {
+        self.attrs.push(' ');
+        self.attrs.push_str(name);
+        self.attrs.push_str("=\"");
+        self.attrs.push_str(&escape(&value.to_string(), true));
+        self.attrs.push('"');
+        self
+    }
+
+    fn opt(self, name: &str, value: Option<impl Display>) -> Self {
+        match value {
+            Some(value) => self.attr(name, value),
+            None => self,
+        }
+    }
+}
+
+/// Escapes markup characters. Whitespace other than spaces is written as
+/// character references in attributes, otherwise the parser would normalize it.
+fn escape(value: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '\n' if attribute => escaped.push_str("&#xa;"),
+            '\t' if attribute => escaped.push_str("&#x9;"),
+            '\r' => escaped.push_str("&#xd;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl ToXml for ScanInfo {
+    fn write(&self, xml: &mut Xml) {
+        xml.empty(
+            &Tag::new("scaninfo")
+                .attr("type", &self.ttype)
+                .attr("protocol", &self.protocol)
+                .attr("numservices", self.numservices)
+                .attr("services", &self.services),
+        );
+    }
+}
+
+impl ToXml for Event {
+    fn write(&self, xml: &mut Xml) {
+        match self {
+            Event::Verbose(Level { level }) => {
+                xml.empty(&Tag::new("verbose").attr("level", level));
+            }
+            Event::Debugging(Level { level }) => {
+                xml.empty(&Tag::new("debugging").attr("level", level));
+            }
+            Event::TaskBegin(task) => xml.empty(&task_tag("taskbegin", task)),
+            Event::TaskEnd(task) => xml.empty(&task_tag("taskend", task)),
+            Event::TaskProgress(TaskProgress {
+                task,
+                time,
+                percent,
+                remaining,
+                etc,
+            }) => xml.empty(
+                &Tag::new("taskprogress")
+                    .attr("task", task)
+                    .attr("time", time)
+                    .attr("percent", percent)
+                    .opt("remaining", remaining.as_ref())
+                    .opt("etc", etc.as_ref()),
+            ),
+        }
+    }
+}
+
+fn task_tag(name: &'static str, task: &Task) -> Tag {
+    Tag::new(name)
+        .attr("task", &task.task)
+        .attr("time", task.time)
+        .opt("extrainfo", task.extrainfo.as_ref())
+}
+
+impl ToXml for Script {
+    fn write(&self, xml: &mut Xml) {
+        let tag = Tag::new("script")
+            .attr("id", &self.id)
+            .attr("output", &self.output);
+
+        match &self.data {
+            Some(value) => {
+                xml.open(&tag);
+                write_script_value(xml, None, value, true);
+                xml.close("script");
+            }
+            None => xml.empty(&tag),
+        }
+    }
+}
+
+/// The root value of a script is written without a wrapping `<table>`.
+fn write_script_value(xml: &mut Xml, key: Option<&str>, value: &ScriptValue, root: bool) {
+    match value {
+        ScriptValue::Scalar(text) => xml.text(&Tag::new("elem").opt("key", key), text),
+        ScriptValue::List(values) => {
+            let tag = Tag::new("table").opt("key", key);
+            if !root {
+                xml.open(&tag);
+            }
+            for value in values {
+                write_script_value(xml, None, value, false);
+            }
+            if !root {
+                xml.close("table");
+            }
+        }
+        ScriptValue::Mixed(MixedTable { keyed, unkeyed }) => {
+            let tag = Tag::new("table").opt("key", key);
+            if !root {
+                xml.open(&tag);
+            }
+            for value in unkeyed {
+                write_script_value(xml, None, value, false);
+            }
+            for (key, value) in keyed {
+                write_script_value(xml, Some(key), value, false);
+            }
+            if !root {
+                xml.close("table");
+            }
+        }
+        ScriptValue::Map(values) => {
+            let tag = Tag::new("table").opt("key", key);
+            if !root {
+                xml.open(&tag);
+            }
+            for (key, value) in values {
+                write_script_value(xml, Some(key), value, false);
+            }
+            if !root {
+                xml.close("table");
+            }
+        }
+    }
+}
+
+impl ToXml for Host {
+    fn write(&self, xml: &mut Xml) {
+        xml.open(
+            &Tag::new("host")
+                .opt("starttime", self.start_time)
+                .opt("endtime", self.end_time)
+                .opt("timedout", self.timed_out),
+        );
+
+        xml.empty(
+            &Tag::new("status")
+                .attr("state", &self.status.state)
+                .opt("reason", self.status.reason.as_ref())
+                .opt("reason_ttl", self.status.reason_ttl),
+        );
+        xml.all(&self.addresses);
+        xml.parent(&Tag::new("hostnames"), self.host_names.iter().flatten());
+
+        if self.ports.is_some() || self.extraports.is_some() {
+            xml.open(&Tag::new("ports"));
+            xml.all(self.extraports.iter().flatten());
+            xml.all(self.ports.iter().flatten());
+            xml.close("ports");
+        }
+
+        xml.all(&self.os);
+        if let Some(uptime) = &self.uptime {
+            xml.empty(
+                &Tag::new("uptime")
+                    .attr("seconds", uptime.seconds)
+                    .opt("lastboot", uptime.lastboot.as_ref()),
+            );
+        }
+        if let Some(distance) = &self.distance {
+            xml.empty(&Tag::new("distance").attr("value", distance.value));
+        }
+        if let Some(sequence) = &self.tcp_sequence {
+            xml.empty(
+                &Tag::new("tcpsequence")
+                    .attr("index", sequence.index)
+                    .attr("difficulty", &sequence.difficulty)
+                    .attr("values", &sequence.values),
+            );
+        }
+        if let Some(sequence) = &self.ip_id_sequence {
+            xml.empty(
+                &Tag::new("ipidsequence")
+                    .attr("class", &sequence.class)
+                    .attr("values", &sequence.values),
+            );
+        }
+        if let Some(sequence) = &self.tcp_ts_sequence {
+            xml.empty(
+                &Tag::new("tcptssequence")
+                    .attr("class", &sequence.class)
+                    .opt("values", sequence.values.as_ref()),
+            );
+        }
+        if let Some(scripts) = &self.host_scripts {
+            xml.scripts("hostscript", scripts);
+        }
+        xml.all(&self.trace);
+        if let Some(times) = &self.times {
+            xml.empty(
+                &Tag::new("times")
+                    .attr("srtt", times.srtt)
+                    .attr("rttvar", times.rttvar)
+                    .attr("to", times.to),
+            );
+        }
+
+        xml.close("host");
+    }
+}
+
+impl ToXml for Address {
+    fn write(&self, xml: &mut Xml) {
+        let tag = match self {
+            Address::IpAddr(addr) => Tag::new("address")
+                .attr("addr", addr)
+                .attr("addrtype", if addr.is_ipv4() { "ipv4" } else { "ipv6" }),
+            Address::MacAddr { addr, vendor } => Tag::new("address")
+                .attr("addr", addr)
+                .attr("addrtype", "mac")
+                .opt("vendor", vendor.as_ref()),
+        };
+        xml.empty(&tag);
+    }
+}
+
+impl ToXml for Hostname {
+    fn write(&self, xml: &mut Xml) {
+        xml.empty(
+            &Tag::new("hostname")
+                .attr("name", &self.name)
+                .attr("type", &self.ttype),
+        );
+    }
+}
+
+impl ToXml for ExtraPorts {
+    fn write(&self, xml: &mut Xml) {
+        let tag = Tag::new("extraports")
+            .attr("state", &self.state)
+            .attr("count", self.count);
+        xml.parent(&tag, self.extrareasons.iter().flatten());
+    }
+}
+
+impl ToXml for ExtraReasons {
+    fn write(&self, xml: &mut Xml) {
+        xml.empty(
+            &Tag::new("extrareasons")
+                .attr("reason", &self.reason)
+                .attr("count", self.count)
+                .opt("proto", self.proto.as_ref())
+                .opt("ports", self.ports.as_ref()),
+        );
+    }
+}
+
+impl ToXml for Port {
+    fn write(&self, xml: &mut Xml) {
+        xml.open(
+            &Tag::new("port")
+                .attr("protocol", &self.protocol)
+                .attr("portid", self.port_number),
+        );
+        self.status.write(xml);
+        xml.all(&self.service);
+        xml.all(self.scripts.iter().flatten());
+        xml.close("port");
+    }
+}
+
+impl ToXml for PortStatus {
+    fn write(&self, xml: &mut Xml) {
+        xml.empty(
+            &Tag::new("state")
+                .attr("state", &self.state)
+                .opt("reason", self.reason.as_ref())
+                .opt("reason_ttl", self.reason_ttl),
+        );
+    }
+}
+
+impl ToXml for Service {
+    fn write(&self, xml: &mut Xml) {
+        let tag = Tag::new("service")
+            .attr("name", &self.name)
+            .opt("product", self.product.as_ref())
+            .opt("version", self.version.as_ref())
+            .opt("extrainfo", self.extra_info.as_ref())
+            .opt("tunnel", self.tunnel.as_ref())
+            .opt("proto", self.proto.as_ref())
+            .opt("rpcnum", self.rpc_num)
+            .opt("lowver", self.low_ver)
+            .opt("highver", self.high_ver)
+            .opt("hostname", self.hostname.as_ref())
+            .opt("ostype", self.os_type.as_ref())
+            .opt("devicetype", self.device_type.as_ref())
+            .opt("servicefp", self.service_fp.as_ref())
+            .opt("method", self.method.as_ref())
+            .opt("conf", self.confidence_level);
+        xml.parent(&tag, self.cpe.iter().flatten());
+    }
+}
+
+impl ToXml for Cpe {
+    fn write(&self, xml: &mut Xml) {
+        xml.text(&Tag::new("cpe"), &self.0);
+    }
+}
+
+impl ToXml for Os {
+    fn write(&self, xml: &mut Xml) {
+        xml.open(&Tag::new("os"));
+        xml.all(&self.portused);
+        xml.all(&self.osmatch);
+        if let Some(osfingerprint) = &self.osfingerprint {
+            xml.empty(&Tag::new("osfingerprint").attr("fingerprint", &osfingerprint.fingerprint));
+        }
+        xml.close("os");
+    }
+}
+
+impl ToXml for PortUsed {
+    fn write(&self, xml: &mut Xml) {
+        xml.empty(
+            &Tag::new("portused")
+                .attr("state", &self.state)
+                .attr("proto", &self.proto)
+                .attr("portid", self.portid),
+        );
+    }
+}
+
+impl ToXml for OsMatch {
+    fn write(&self, xml: &mut Xml) {
+        let tag = Tag::new("osmatch")
+            .attr("name", &self.name)
+            .opt("accuracy", self.accuracy)
+            .opt("line", self.line);
+        xml.parent(&tag, self.osclass.iter().flatten());
+    }
+}
+
+impl ToXml for OsClass {
+    fn write(&self, xml: &mut Xml) {
+        let tag = Tag::new("osclass")
+            .attr("type", &self.ttype)
+            .attr("vendor", &self.vendor)
+            .attr("osfamily", &self.osfamily)
+            .opt("osgen", self.osgen.as_ref())
+            .attr("accuracy", self.accuracy);
+        xml.parent(&tag, self.cpe.iter().flatten());
+    }
+}
+
+impl ToXml for Trace {
+    fn write(&self, xml: &mut Xml) {
+        let tag = Tag::new("trace")
+            .opt("port", self.port)
+            .opt("proto", self.proto.as_ref());
+        xml.parent(&tag, &self.hops);
+    }
+}
+
+impl ToXml for Hop {
+    fn write(&self, xml: &mut Xml) {
+        xml.empty(
+            &Tag::new("hop")
+                .attr("ttl", self.ttl)
+                .opt("ipaddr", self.ipaddr)
+                .opt("rtt", self.rtt)
+                .opt("host", self.host.as_ref()),
+        );
+    }
+}
+
+impl ToXml for RunStats {
+    fn write(&self, xml: &mut Xml) {
+        xml.open(&Tag::new("runstats"));
+        xml.all(&self.finished);
+        xml.all(&self.hosts);
+        xml.close("runstats");
+    }
+}
+
+impl ToXml for Finished {
+    fn write(&self, xml: &mut Xml) {
+        xml.empty(
+            &Tag::new("finished")
+                .attr("time", self.time)
+                .opt("timestr", self.timestr.as_ref())
+                .opt("summary", self.summary.as_ref())
+                .opt("elapsed", self.elapsed)
+                .opt("exit", self.exit.as_ref())
+                .opt("errormsg", self.errormsg.as_ref()),
+        );
+    }
+}
+
+impl ToXml for Hosts {
+    fn write(&self, xml: &mut Xml) {
+        xml.empty(
+            &Tag::new("hosts")
+                .attr("up", self.up)
+                .attr("down", self.down)
+                .attr("total", self.total),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::ffi::OsStr;
+    use std::fs;
+
+    use error_stack::ResultExt;
+
+    use crate::{fixture, NmapRun};
+
+    #[test]
+    fn mixed_script_table() {
+        let xml = fixture::host(
+            r#"<hostscript><script id="x" output="..."><elem>a</elem><elem key="1">keyed</elem><elem>b</elem></script></hostscript>"#,
+        );
+        let report = NmapRun::parse(&xml).unwrap();
+        let written = report.to_xml();
+
+        // Unkeyed children get no invented `key`, and come back in their order
+        assert!(written.contains("<elem>a</elem>\n<elem>b</elem>\n<elem key=\"1\">keyed</elem>"));
+        assert_eq!(
+            format!("{report:?}"),
+            format!("{:?}", NmapRun::parse(&written).unwrap())
+        );
+    }
+
+    #[test]
+    fn xml_round_trip() -> Result<(), Box<dyn Error>> {
+        for entry in fs::read_dir("reports")? {
+            let path = entry?.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "xml") {
+                let filename = path
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .unwrap_or("unknown");
+                let report = NmapRun::parse_and_fix(fs::read_to_string(&path)?)
+                    .attach_printable(format!("filename: {filename}"))?;
+
+                let written = NmapRun::parse(&report.to_xml())
+                    .attach_printable(format!("filename: {filename}"))?;
+
+                assert_eq!(
+                    format!("{report:?}"),
+                    format!("{written:?}"),
+                    "filename: {filename}"
+                );
+
+                // `n2j --to xml` takes the JSON output
+                let json = serde_json::to_string(&report)?;
+                let written = NmapRun::parse(&serde_json::from_str::<NmapRun>(&json)?.to_xml())
+                    .attach_printable(format!("filename: {filename}"))?;
+
+                assert_eq!(
+                    format!("{report:?}"),
+                    format!("{written:?}"),
+                    "filename: {filename}"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}